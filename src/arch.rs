@@ -0,0 +1,164 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The architectures whose DWARF register numbering is known to this crate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Arch {
+    X86_64,
+    AArch64,
+}
+
+/// DWARF register numbers 0-16 of the x86_64 System V ABI.
+const X86_64_REGISTERS: [&str; 17] = [
+    "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip",
+];
+
+/// DWARF register numbers 0-31 of the AArch64 ABI.
+const AARCH64_REGISTERS: [&str; 32] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14",
+    "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27",
+    "x28", "x29", "x30", "sp",
+];
+
+/// DWARF register numbers 17-32 of the x86_64 System V ABI. The same numbers are
+/// used for the full 32 bytes of the AVX registers (ymm0-ymm15).
+const X86_64_VECTOR_REGISTERS: [&str; 16] = [
+    "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10",
+    "xmm11", "xmm12", "xmm13", "xmm14", "xmm15",
+];
+
+/// DWARF register numbers 64-95 of the AArch64 ABI.
+const AARCH64_VECTOR_REGISTERS: [&str; 32] = [
+    "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "v10", "v11", "v12", "v13", "v14",
+    "v15", "v16", "v17", "v18", "v19", "v20", "v21", "v22", "v23", "v24", "v25", "v26", "v27",
+    "v28", "v29", "v30", "v31",
+];
+
+const X86_64_FIRST_VECTOR_REGISTER: u16 = 17;
+const AARCH64_FIRST_VECTOR_REGISTER: u16 = 64;
+
+impl Arch {
+    /// Get the architecture of an ELF file based on its `e_machine` value.
+    #[cfg(feature = "from-elf")]
+    pub fn from_elf_machine(e_machine: u16) -> Option<Arch> {
+        match e_machine {
+            goblin::elf::header::EM_X86_64 => Some(Arch::X86_64),
+            goblin::elf::header::EM_AARCH64 => Some(Arch::AArch64),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the register with the DWARF register number `dwarf_regnum`.
+    /// Returns None if the register is unknown for this architecture.
+    pub fn register_name(&self, dwarf_regnum: u16) -> Option<&'static str> {
+        if let Some(idx) = self.vector_register_index(dwarf_regnum) {
+            return Some(match self {
+                Arch::X86_64 => X86_64_VECTOR_REGISTERS[idx],
+                Arch::AArch64 => AARCH64_VECTOR_REGISTERS[idx],
+            });
+        }
+        let registers: &[&str] = match self {
+            Arch::X86_64 => &X86_64_REGISTERS,
+            Arch::AArch64 => &AARCH64_REGISTERS,
+        };
+        registers.get(dwarf_regnum as usize).copied()
+    }
+
+    /// Get the name of the register with the DWARF register number `dwarf_regnum`
+    /// when `size` bytes of it are accessed. This differs from `register_name` for
+    /// vector registers only, e.g., ymm0 is the 32 byte view of xmm0, and d0 the
+    /// 8 byte view of v0.
+    pub fn sized_register_name(&self, dwarf_regnum: u16, size: u16) -> Option<String> {
+        let idx = match self.vector_register_index(dwarf_regnum) {
+            Some(idx) => idx,
+            None => return self.register_name(dwarf_regnum).map(str::to_owned),
+        };
+        let prefix = match (self, size) {
+            (Arch::X86_64, 32) => "ymm",
+            (Arch::X86_64, _) => "xmm",
+            (Arch::AArch64, 1) => "b",
+            (Arch::AArch64, 2) => "h",
+            (Arch::AArch64, 4) => "s",
+            (Arch::AArch64, 8) => "d",
+            (Arch::AArch64, 16) => "q",
+            (Arch::AArch64, _) => "v",
+        };
+        Some(format!("{}{}", prefix, idx))
+    }
+
+    /// Get the width in bytes of the register with the DWARF register number
+    /// `dwarf_regnum`. Returns None if the register is unknown for this architecture.
+    pub fn register_size(&self, dwarf_regnum: u16) -> Option<u16> {
+        if self.is_vector_register(dwarf_regnum) {
+            return Some(match self {
+                Arch::X86_64 => 32,
+                Arch::AArch64 => 16,
+            });
+        }
+        self.register_name(dwarf_regnum).map(|_| 8)
+    }
+
+    /// Whether `dwarf_regnum` is the DWARF register number of a vector register
+    /// (xmm/ymm on x86_64, v on AArch64).
+    pub fn is_vector_register(&self, dwarf_regnum: u16) -> bool {
+        self.vector_register_index(dwarf_regnum).is_some()
+    }
+
+    /// Get the index of the vector register with the DWARF register number
+    /// `dwarf_regnum`, e.g., 1 for xmm1.
+    fn vector_register_index(&self, dwarf_regnum: u16) -> Option<usize> {
+        let (first, count) = match self {
+            Arch::X86_64 => (X86_64_FIRST_VECTOR_REGISTER, X86_64_VECTOR_REGISTERS.len()),
+            Arch::AArch64 => (
+                AARCH64_FIRST_VECTOR_REGISTER,
+                AARCH64_VECTOR_REGISTERS.len(),
+            ),
+        };
+        let idx = dwarf_regnum.checked_sub(first)? as usize;
+        (idx < count).then(|| idx)
+    }
+
+    /// The DWARF register number of the vector register with the index `idx`,
+    /// e.g., 18 for xmm1 on x86_64.
+    pub fn vector_register(&self, idx: usize) -> Option<u16> {
+        let (first, count) = match self {
+            Arch::X86_64 => (X86_64_FIRST_VECTOR_REGISTER, X86_64_VECTOR_REGISTERS.len()),
+            Arch::AArch64 => (
+                AARCH64_FIRST_VECTOR_REGISTER,
+                AARCH64_VECTOR_REGISTERS.len(),
+            ),
+        };
+        (idx < count).then(|| first + idx as u16)
+    }
+
+    /// The DWARF register number of the stack pointer.
+    pub fn stack_pointer(&self) -> u16 {
+        match self {
+            Arch::X86_64 => 7,
+            Arch::AArch64 => 31,
+        }
+    }
+
+    /// The DWARF register number of the program counter, if it has one (rip on
+    /// x86_64).
+    pub fn program_counter(&self) -> Option<u16> {
+        match self {
+            Arch::X86_64 => Some(16),
+            Arch::AArch64 => None,
+        }
+    }
+
+    /// The DWARF register numbers of the general purpose registers a callee must
+    /// preserve according to the default calling convention (System V and AAPCS64).
+    /// The stack pointer is not included.
+    pub fn callee_saved_registers(&self) -> &'static [u16] {
+        match self {
+            // rbx, rbp, r12-r15
+            Arch::X86_64 => &[3, 6, 12, 13, 14, 15],
+            // x19-x29
+            Arch::AArch64 => &[19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29],
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fs, path::Path, str};
+
+use crate::{BinaryFormat, ParseOptions, ParsingError, StackMap};
+
+const ARCHIVE_MAGIC: &[u8] = b"!<arch>\n";
+const THIN_ARCHIVE_MAGIC: &[u8] = b"!<thin>\n";
+const MEMBER_HEADER_SIZE: usize = 60;
+
+/// A member of an `ar` archive.
+struct Member<'a> {
+    name: String,
+    /// The content of the member, or None if the archive is thin, i.e., the member
+    /// is stored in a separate file.
+    data: Option<&'a [u8]>,
+}
+
+/// Check whether `bytes` is a (possibly thin) `ar` archive.
+pub(crate) fn is_archive(bytes: &[u8]) -> bool {
+    bytes.starts_with(ARCHIVE_MAGIC) || bytes.starts_with(THIN_ARCHIVE_MAGIC)
+}
+
+fn malformed(msg: &str) -> ParsingError {
+    ParsingError::Malformed(format!("Malformed archive: {}", msg))
+}
+
+/// Parse a decimal header field that is padded with spaces.
+fn parse_decimal(field: &[u8]) -> Result<usize, ParsingError> {
+    str::from_utf8(field)
+        .ok()
+        .and_then(|field| field.trim_end().parse().ok())
+        .ok_or_else(|| malformed("invalid number in member header"))
+}
+
+/// Get the object file members of the archive `bytes`. Both the GNU (SysV) and
+/// BSD variant of the format are supported.
+fn parse_members(bytes: &[u8]) -> Result<Vec<Member<'_>>, ParsingError> {
+    let thin = bytes.starts_with(THIN_ARCHIVE_MAGIC);
+    let mut members = Vec::new();
+    let mut long_names: &[u8] = &[];
+    let mut offset = ARCHIVE_MAGIC.len();
+
+    while offset < bytes.len() {
+        let header = bytes
+            .get(offset..offset + MEMBER_HEADER_SIZE)
+            .ok_or_else(|| malformed("truncated member header"))?;
+        if &header[58..60] != b"`\n" {
+            return Err(malformed("invalid member header magic"));
+        }
+        let raw_name = str::from_utf8(&header[..16])
+            .map_err(|_| malformed("invalid member name"))?
+            .trim_end();
+        let size = parse_decimal(&header[48..58])?;
+        let data_start = offset + MEMBER_HEADER_SIZE;
+
+        // The members of thin archives are stored outside of the archive, except
+        // for the symbol table and the name table.
+        let is_special = raw_name.starts_with('/') && !raw_name[1..].starts_with(char::is_numeric);
+        let stored_size = if thin && !is_special { 0 } else { size };
+        let data = bytes
+            .get(data_start..data_start + stored_size)
+            .ok_or_else(|| malformed("member exceeds the archive"))?;
+        // Members are aligned to two bytes.
+        offset = data_start + stored_size + stored_size % 2;
+
+        let (name, data) = match raw_name {
+            // The GNU name table of names that are longer than 15 characters.
+            "//" => {
+                long_names = data;
+                continue;
+            }
+            // Symbol tables
+            "/" | "/SYM64/" | "__.SYMDEF" | "__.SYMDEF SORTED" => continue,
+            _ if raw_name.starts_with('/') => {
+                let name_offset = parse_decimal(raw_name[1..].as_bytes())?;
+                let name = long_names
+                    .get(name_offset..)
+                    .ok_or_else(|| malformed("member name exceeds the name table"))?;
+                let end = name
+                    .windows(2)
+                    .position(|w| w == b"/\n")
+                    .or_else(|| name.iter().position(|b| *b == b'\n'))
+                    .unwrap_or(name.len());
+                let name =
+                    str::from_utf8(&name[..end]).map_err(|_| malformed("invalid member name"))?;
+                (name.to_owned(), data)
+            }
+            // BSD stores long names in front of the member data.
+            _ if raw_name.starts_with("#1/") => {
+                let name_len = parse_decimal(raw_name[3..].as_bytes())?;
+                if name_len > data.len() {
+                    return Err(malformed("member name exceeds the member"));
+                }
+                let (name, data) = data.split_at(name_len);
+                let name = str::from_utf8(name)
+                    .map_err(|_| malformed("invalid member name"))?
+                    .trim_end_matches('\0');
+                (name.to_owned(), data)
+            }
+            _ => (raw_name.trim_end_matches('/').to_owned(), data),
+        };
+
+        members.push(Member {
+            name,
+            data: if thin { None } else { Some(data) },
+        });
+    }
+    Ok(members)
+}
+
+impl StackMap {
+    /// Parse the stackmap(s) of all members of the `ar` archive `bytes`. Members of
+    /// thin archives are read from the files they refer to, relative to `dir`.
+    fn from_archive(
+        bytes: &[u8],
+        dir: Option<&Path>,
+        options: &ParseOptions,
+    ) -> Result<Vec<(String, Vec<StackMap>)>, ParsingError> {
+        let mut result = Vec::new();
+        for member in parse_members(bytes)? {
+            let thin_data;
+            let data = match (member.data, dir) {
+                (Some(data), _) => data,
+                (None, Some(dir)) => {
+                    thin_data = fs::read(dir.join(&member.name))?;
+                    &thin_data[..]
+                }
+                (None, None) => {
+                    return Err(ParsingError::Malformed(
+                        "Thin archives can only be read from a path".to_owned(),
+                    ))
+                }
+            };
+
+            // Skip members that are no object files, e.g., LLVM bitcode.
+            match BinaryFormat::detect(data) {
+                BinaryFormat::Elf | BinaryFormat::MachO | BinaryFormat::Pe => (),
+                BinaryFormat::Archive | BinaryFormat::Raw => continue,
+            }
+            match StackMap::from_bytes_with_options(data, options) {
+                Ok(maps) => result.push((member.name, maps)),
+                Err(ParsingError::StackMapSectionNotFound) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Parse the stackmap(s) of all members of the `ar` archive (`.a`) `bytes`.
+    /// The stackmaps are returned together with the name of the member they belong
+    /// to, members without a stackmap are omitted. Thin archives are not supported,
+    /// since their members are stored outside of the archive (see `from_archive_path`).
+    pub fn from_archive_bytes(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Vec<(String, Vec<StackMap>)>, ParsingError> {
+        if !is_archive(bytes) {
+            return Err(malformed("invalid magic"));
+        }
+        StackMap::from_archive(bytes, None, options)
+    }
+
+    /// Same as `from_archive_bytes`, but read the archive from `path`. The members
+    /// of thin archives are read from their paths relative to the archive.
+    pub fn from_archive_path<T: AsRef<Path>>(
+        path: T,
+        options: &ParseOptions,
+    ) -> Result<Vec<(String, Vec<StackMap>)>, ParsingError> {
+        let bytes = fs::read(path.as_ref())?;
+        if !is_archive(&bytes) {
+            return Err(malformed("invalid magic"));
+        }
+        let dir = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        StackMap::from_archive(&bytes, Some(dir), options)
+    }
+}
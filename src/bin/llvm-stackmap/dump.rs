@@ -0,0 +1,127 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
+
+use llvm_stackmap::StackMap;
+
+use crate::{parse_u64, Filter, Format};
+
+/// Create a copy of `map` that only contains the functions and records matching `filter`.
+/// Functions without any matching record are dropped, unless no record filter is set.
+// `Option::is_none_or` requires Rust 1.82.
+#[allow(clippy::unnecessary_map_or)]
+pub fn filter_map(map: &StackMap, filter: &Filter) -> StackMap {
+    let mut filtered = map.clone();
+    if filter.function.is_none() && filter.id.is_none() && filter.address.is_none() {
+        return filtered;
+    }
+
+    filtered.stk_size_records.clear();
+    filtered.stk_map_records.clear();
+    filtered.num_functions = 0;
+    filtered.num_records = 0;
+    // Large constants are kept as they are, such that ConstIndex locations stay valid.
+    for (function, records) in map.function_records() {
+        if let Some(selected) = filter.function.as_deref() {
+            let by_address = parse_u64(selected).is_ok_and(|a| a == function.function_address);
+            let by_name = map.function_name(function) == Some(selected)
+                || map.demangled_function_name(function).as_deref() == Some(selected);
+            if !by_address && !by_name {
+                continue;
+            }
+        }
+
+        let records = records
+            .iter()
+            .filter(|r| {
+                filter
+                    .id
+                    .as_ref()
+                    .map_or(true, |id| id.contains(&r.patch_point_id))
+            })
+            .filter(|r| {
+                let address = r.address(function);
+                filter
+                    .address
+                    .as_ref()
+                    .map_or(true, |range| range.contains(&address))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if records.is_empty() && (filter.id.is_some() || filter.address.is_some()) {
+            continue;
+        }
+        // The counts of the source map are bound by u32, thus this can not fail.
+        filtered.push_function(*function, records).unwrap();
+    }
+    filtered
+}
+
+pub fn dump(out: &mut impl Write, maps: &[StackMap], format: Format) -> io::Result<()> {
+    match format {
+        Format::Readobj => {
+            for map in maps {
+                map.write_readobj(out)?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, maps)?;
+            writeln!(out)?;
+        }
+        Format::Csv => write_csv(out, maps)?,
+    }
+    Ok(())
+}
+
+/// Write one row per location. Records without locations are written as a
+/// single row with empty location columns.
+fn write_csv(out: &mut impl Write, maps: &[StackMap]) -> io::Result<()> {
+    writeln!(
+        out,
+        "map,function_address,function_name,stack_size,patch_point_id,instruction_offset,\
+         location,type,size,dwarf_regnum,offset_or_constant,num_live_outs"
+    )?;
+    for (map_idx, map) in maps.iter().enumerate() {
+        for (function, records) in map.function_records() {
+            for record in records {
+                let prefix = format!(
+                    "{},{:#x},{},{},{},{}",
+                    map_idx,
+                    function.function_address,
+                    csv_field(&map.demangled_function_name(function).unwrap_or_default()),
+                    function.stack_size,
+                    record.patch_point_id,
+                    record.instruction_offset
+                );
+                if record.locations().is_empty() {
+                    writeln!(out, "{},,,,,,{}", prefix, record.live_out_count())?;
+                }
+                for (idx, loc) in record.locations().iter().enumerate() {
+                    writeln!(
+                        out,
+                        "{},{},{:?},{},{},{},{}",
+                        prefix,
+                        idx + 1,
+                        loc.loc_type,
+                        loc.loc_size,
+                        loc.dwarf_regnum,
+                        loc.offset_or_constant,
+                        record.live_out_count()
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Quote `field` as described by RFC 4180 if it contains a separator, quote or
+/// line break, e.g., the parameter list of a demangled C++ function name.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
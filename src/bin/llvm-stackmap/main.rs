@@ -0,0 +1,156 @@
+use std::{
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use llvm_stackmap::{ParsingError, StackMap};
+
+mod dump;
+
+/// Inspect the LLVM stack maps embedded into binaries.
+#[derive(Parser, Debug)]
+#[command(name = "llvm-stackmap", version)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the stack map(s) of a binary.
+    Dump(DumpArgs),
+    /// Compare the stack map(s) of two builds of a binary.
+    Diff(DiffArgs),
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// The same notation as used by `llvm-readobj --stackmap`.
+    Readobj,
+    Json,
+    /// One row per location.
+    Csv,
+}
+
+#[derive(clap::Args, Debug)]
+struct DumpArgs {
+    /// The binary to read the stack map(s) from.
+    path: PathBuf,
+    #[arg(long, value_enum, default_value_t = Format::Readobj)]
+    format: Format,
+    #[command(flatten)]
+    filter: Filter,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffFormat {
+    /// One line per change.
+    Text,
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    /// The binary of the old build.
+    old: PathBuf,
+    /// The binary of the new build.
+    new: PathBuf,
+    #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+    format: DiffFormat,
+}
+
+/// Restricts which functions and records are printed.
+#[derive(clap::Args, Debug, Default)]
+pub struct Filter {
+    /// Only include the function with this symbol name or address.
+    #[arg(long)]
+    function: Option<String>,
+    /// Only include records whose patch point ID is in the range (e.g. `5`, `5..10`).
+    #[arg(long, value_parser = parse_range)]
+    id: Option<Range<u64>>,
+    /// Only include records whose address is in the range (e.g. `0x1000..0x2000`).
+    #[arg(long, value_parser = parse_range)]
+    address: Option<Range<u64>>,
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal number.
+fn parse_u64(s: &str) -> Result<u64, String> {
+    let res = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    };
+    res.map_err(|err| format!("Invalid number {:?}: {}", s, err))
+}
+
+/// Parse either a single number or a half-open range `start..end`.
+fn parse_range(s: &str) -> Result<Range<u64>, String> {
+    match s.split_once("..") {
+        Some((start, end)) => Ok(parse_u64(start)?..parse_u64(end)?),
+        None => {
+            let val = parse_u64(s)?;
+            Ok(val..val.saturating_add(1))
+        }
+    }
+}
+
+fn load(path: &PathBuf) -> Result<Vec<StackMap>, String> {
+    StackMap::from_path(path).map_err(|err| match err {
+        ParsingError::StackMapSectionNotFound => {
+            format!("{}: no stack map section found", path.display())
+        }
+        err => format!("{}: failed to parse stack map: {:?}", path.display(), err),
+    })
+}
+
+/// Output errors caused by a closed pipe (e.g. `| head`) are not reported.
+fn output_error(err: io::Error) -> Option<String> {
+    match err.kind() {
+        io::ErrorKind::BrokenPipe => None,
+        _ => Some(format!("failed to write output: {}", err)),
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let res = match args.command {
+        Command::Dump(args) => {
+            let maps = load(&args.path)?;
+            let maps = maps
+                .iter()
+                .map(|map| dump::filter_map(map, &args.filter))
+                .collect::<Vec<_>>();
+            dump::dump(&mut out, &maps, args.format)
+        }
+        Command::Diff(args) => {
+            let old = load(&args.old)?;
+            let new = load(&args.new)?;
+            let changes = StackMap::diff(&old, &new);
+            match args.format {
+                DiffFormat::Text => changes
+                    .iter()
+                    .try_for_each(|change| writeln!(out, "{}", change)),
+                DiffFormat::Json => serde_json::to_writer_pretty(&mut out, &changes)
+                    .map_err(io::Error::from)
+                    .and_then(|_| writeln!(out)),
+            }
+        }
+    };
+    match res.and_then(|_| out.flush()) {
+        Err(err) => output_error(err).map_or(Ok(()), Err),
+        Ok(()) => Ok(()),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,78 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Arch, LiveOut, StkMapRecord};
+
+/// The calling convention of the code that is inserted at a patch point, e.g.,
+/// of the function called by it. It determines which registers the inserted code
+/// may clobber.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CallingConvention {
+    /// The System V ABI (x86_64 only).
+    SysV,
+    /// The procedure call standard of AArch64 (AArch64 only).
+    Aapcs64,
+    /// LLVM's `preserve_all`, which preserves almost all registers.
+    PreserveAll,
+    /// LLVM's `anyregcc`, which preserves all registers.
+    AnyReg,
+}
+
+/// x86_64 System V: rax, rdx, rcx, rsi, rdi, r8-r11.
+const X86_64_SYSV_CLOBBERED: [u16; 9] = [0, 1, 2, 4, 5, 8, 9, 10, 11];
+/// x86_64 `preserve_all`: r11 is used as scratch register.
+const X86_64_PRESERVE_ALL_CLOBBERED: [u16; 1] = [11];
+/// The link register of AArch64, which is written by the call instruction itself.
+const AARCH64_LR: u16 = 30;
+
+impl CallingConvention {
+    /// Whether calling a function of this convention might modify any of the lower
+    /// `size` bytes of the register `dwarf_regnum`. Returns None if the
+    /// convention is not defined for `arch`.
+    pub fn clobbers(&self, arch: Arch, dwarf_regnum: u16, size: u16) -> Option<bool> {
+        let clobbered = match (arch, self) {
+            (Arch::X86_64, CallingConvention::SysV) => {
+                // All xmm/ymm registers are caller-saved.
+                X86_64_SYSV_CLOBBERED.contains(&dwarf_regnum)
+                    || arch.is_vector_register(dwarf_regnum)
+            }
+            (Arch::X86_64, CallingConvention::PreserveAll) => {
+                X86_64_PRESERVE_ALL_CLOBBERED.contains(&dwarf_regnum)
+            }
+            (Arch::AArch64, CallingConvention::Aapcs64) => match dwarf_regnum {
+                // x0-x18 and the link register.
+                0..=18 | AARCH64_LR => true,
+                // Only the lower 8 bytes of v8-v15 are preserved.
+                72..=79 => size > 8,
+                _ => arch.is_vector_register(dwarf_regnum),
+            },
+            (Arch::AArch64, CallingConvention::PreserveAll) => match dwarf_regnum {
+                // x0-x8, x16-x18, v0-v7 and the link register.
+                0..=8 | 16..=18 | 64..=71 | AARCH64_LR => true,
+                _ => false,
+            },
+            (Arch::X86_64, CallingConvention::AnyReg) => false,
+            (Arch::AArch64, CallingConvention::AnyReg) => dwarf_regnum == AARCH64_LR,
+            (Arch::X86_64, CallingConvention::Aapcs64)
+            | (Arch::AArch64, CallingConvention::SysV) => return None,
+        };
+        Some(clobbered)
+    }
+}
+
+impl StkMapRecord {
+    /// Get the live-outs of this record that code inserted at the patch point must
+    /// save and restore if it calls a function of the calling convention `cc`,
+    /// i.e., the live-outs that might be clobbered by such a call. Returns None if
+    /// `cc` is not defined for `arch`.
+    pub fn live_outs_to_save(&self, arch: Arch, cc: CallingConvention) -> Option<Vec<LiveOut>> {
+        let mut to_save = Vec::new();
+        for live_out in self.live_outs() {
+            if cc.clobbers(arch, live_out.dwarf_regnum(), live_out.size().into())? {
+                to_save.push(*live_out);
+            }
+        }
+        Some(to_save)
+    }
+}
@@ -0,0 +1,221 @@
+use std::{fs, path::Path, rc::Rc};
+
+use gimli::{
+    BaseAddresses, CfaRule, DebugFrame, EhFrame, EndianRcSlice, Register, RegisterRule,
+    RunTimeEndian, UnwindContext, UnwindSection, UnwindTableRow,
+};
+use goblin::elf::Elf;
+
+use crate::{Arch, MemoryProvider, ParsingError, Registers, StackMap, WalkStop};
+
+type Reader = EndianRcSlice<RunTimeEndian>;
+
+/// Unwinds frames using the DWARF call frame information (CFI) of a binary,
+/// i.e., its `.eh_frame` and `.debug_frame` sections. In contrast to unwinding
+/// via `stack_size`, this also works for functions with dynamically sized frames
+/// and recovers the callee-saved registers of the caller.
+pub struct CfiUnwinder {
+    eh_frame: Option<(EhFrame<Reader>, BaseAddresses)>,
+    debug_frame: Option<DebugFrame<Reader>>,
+    load_bias: u64,
+}
+
+/// Find the unwind table row for `address` in `section`, together with the
+/// return address register of its CIE. Returns None if there is no CFI for the
+/// address.
+fn find_row<'ctx, S: UnwindSection<Reader>>(
+    section: &S,
+    bases: &BaseAddresses,
+    ctx: &'ctx mut UnwindContext<usize>,
+    address: u64,
+) -> gimli::Result<Option<(&'ctx UnwindTableRow<usize>, Register)>> {
+    let fde = match section.fde_for_address(bases, address, S::cie_from_offset) {
+        Ok(fde) => fde,
+        Err(gimli::Error::NoUnwindInfoForAddress) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    match fde.unwind_info_for_address(section, bases, ctx, address) {
+        Ok(row) => Ok(Some((row, fde.cie().return_address_register()))),
+        Err(gimli::Error::NoUnwindInfoForAddress) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn read_u64(mem: &impl MemoryProvider, address: u64) -> Result<u64, WalkStop> {
+    let mut buf = [0u8; 8];
+    if !mem.read_memory(address, &mut buf) {
+        return Err(WalkStop::MemoryUnreadable(address));
+    }
+    Ok(u64::from_le_bytes(buf))
+}
+
+impl CfiUnwinder {
+    /// Create an unwinder for the CFI contained in `elf`. `bytes` must be the
+    /// content of the file `elf` was parsed from.
+    pub fn from_elf(elf: &Elf, bytes: &[u8]) -> Result<CfiUnwinder, ParsingError> {
+        let endian = if elf.little_endian {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let address_size = if elf.is_64 { 8 } else { 4 };
+        let load = |name: &str| -> Result<Option<Reader>, ParsingError> {
+            Ok(StackMap::read_section(elf, bytes, name)?
+                .map(|data| EndianRcSlice::new(Rc::from(&*data), endian)))
+        };
+        let section_address =
+            |name: &str| StackMap::get_section_header(elf, name).map(|section| section.sh_addr);
+
+        let eh_frame = load(".eh_frame")?.map(|data| {
+            let mut eh_frame = EhFrame::from(data);
+            eh_frame.set_address_size(address_size);
+            let mut bases = BaseAddresses::default();
+            if let Some(address) = section_address(".eh_frame") {
+                bases = bases.set_eh_frame(address);
+            }
+            if let Some(address) = section_address(".eh_frame_hdr") {
+                bases = bases.set_eh_frame_hdr(address);
+            }
+            if let Some(address) = section_address(".text") {
+                bases = bases.set_text(address);
+            }
+            if let Some(address) = section_address(".got") {
+                bases = bases.set_got(address);
+            }
+            (eh_frame, bases)
+        });
+        let debug_frame = load(".debug_frame")?.map(|data| {
+            let mut debug_frame = DebugFrame::from(data);
+            debug_frame.set_address_size(address_size);
+            debug_frame
+        });
+
+        Ok(CfiUnwinder {
+            eh_frame,
+            debug_frame,
+            load_bias: 0,
+        })
+    }
+
+    /// Create an unwinder for the CFI of the binary `path` points to.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Result<CfiUnwinder, ParsingError> {
+        let bytes = fs::read(path)?;
+        let elf = Elf::parse(&bytes)?;
+        CfiUnwinder::from_elf(&elf, &bytes)
+    }
+
+    /// Set the offset between runtime and link-time addresses of the binary,
+    /// i.e., the base address of a PIE binary.
+    pub fn with_load_bias(mut self, load_bias: u64) -> CfiUnwinder {
+        self.load_bias = load_bias;
+        self
+    }
+
+    /// Get the registers of the caller of the frame described by `regs`. If the
+    /// frame is not the innermost one (`is_innermost`), its program counter is a
+    /// return address, which might point behind the last instruction of the
+    /// function. Returns None if there is no CFI for the program counter.
+    ///
+    /// Callee-saved registers that are not mentioned by the CFI keep their value.
+    /// On AArch64, the return address of frames that did not save it is taken
+    /// from the link register.
+    pub fn unwind(
+        &self,
+        arch: Arch,
+        regs: &Registers,
+        is_innermost: bool,
+        mem: &impl MemoryProvider,
+    ) -> Result<Option<Registers>, WalkStop> {
+        let address = regs.pc.wrapping_sub(self.load_bias);
+        let address = if is_innermost {
+            address
+        } else {
+            address.wrapping_sub(1)
+        };
+
+        let mut ctx = UnwindContext::new();
+        if let Some((eh_frame, bases)) = &self.eh_frame {
+            match find_row(eh_frame, bases, &mut ctx, address) {
+                Ok(Some((row, ra))) => return self.apply_row(arch, row, ra, regs, mem).map(Some),
+                Ok(None) => (),
+                Err(_) => return Err(WalkStop::UnsupportedCfi(regs.pc)),
+            }
+        }
+        if let Some(debug_frame) = &self.debug_frame {
+            let bases = BaseAddresses::default();
+            match find_row(debug_frame, &bases, &mut ctx, address) {
+                Ok(Some((row, ra))) => return self.apply_row(arch, row, ra, regs, mem).map(Some),
+                Ok(None) => (),
+                Err(_) => return Err(WalkStop::UnsupportedCfi(regs.pc)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Compute the registers of the caller according to the unwind table `row`.
+    fn apply_row(
+        &self,
+        arch: Arch,
+        row: &UnwindTableRow<usize>,
+        return_address_register: Register,
+        regs: &Registers,
+        mem: &impl MemoryProvider,
+    ) -> Result<Registers, WalkStop> {
+        let cfa = match row.cfa() {
+            CfaRule::RegisterAndOffset { register, offset } => regs
+                .get(register.0)
+                .ok_or(WalkStop::RegisterUnavailable(register.0))?
+                .wrapping_add(*offset as u64),
+            CfaRule::Expression(_) => return Err(WalkStop::UnsupportedCfi(regs.pc)),
+        };
+
+        let mut caller = Registers::new(0);
+        for regnum in arch.callee_saved_registers() {
+            if let Some(value) = regs.get(*regnum) {
+                caller.set(*regnum, value);
+            }
+        }
+        for (register, rule) in row.registers() {
+            let value = match rule {
+                RegisterRule::SameValue => regs.get(register.0),
+                RegisterRule::Offset(offset) => {
+                    Some(read_u64(mem, cfa.wrapping_add(*offset as u64))?)
+                }
+                RegisterRule::ValOffset(offset) => Some(cfa.wrapping_add(*offset as u64)),
+                RegisterRule::Register(other) => regs.get(other.0),
+                RegisterRule::Constant(value) => Some(*value),
+                // DWARF expressions are not supported.
+                _ => None,
+            };
+            match value {
+                Some(value) => caller.set(register.0, value),
+                None => {
+                    caller.remove(register.0);
+                }
+            }
+        }
+
+        let explicit_rule = row
+            .registers()
+            .any(|(register, _)| *register == return_address_register);
+        let return_address = if explicit_rule {
+            caller.get(return_address_register.0)
+        } else if arch == Arch::AArch64 {
+            // Leaf functions do not save the link register, which thus still
+            // holds the return address.
+            regs.get(return_address_register.0)
+        } else {
+            None
+        };
+        match return_address {
+            Some(return_address) if return_address != 0 => caller.pc = return_address,
+            _ => return Err(WalkStop::EndOfStack(regs.pc)),
+        }
+        // The return address column is no real register on x86_64.
+        if arch == Arch::X86_64 {
+            caller.remove(return_address_register.0);
+        }
+        caller.set(arch.stack_pointer(), cfa);
+        Ok(caller)
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    convert::TryInto,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use goblin::elf::{note::NT_GNU_BUILD_ID, Elf};
+
+use crate::{ParsingError, StackMap};
+
+/// Locates the separate debug file of a stripped binary, either via the build ID
+/// (`NT_GNU_BUILD_ID`) or the `.gnu_debuglink` section of the binary. This follows
+/// the lookup rules used by GDB.
+#[derive(Debug, Clone)]
+pub struct DebugFileLocator {
+    /// Global debug directories, e.g., `/usr/lib/debug`.
+    pub debug_dirs: Vec<PathBuf>,
+}
+
+/// By default, only `/usr/lib/debug` is searched.
+impl Default for DebugFileLocator {
+    fn default() -> Self {
+        DebugFileLocator {
+            debug_dirs: vec![PathBuf::from("/usr/lib/debug")],
+        }
+    }
+}
+
+/// The content of a `.gnu_debuglink` section.
+struct DebugLink {
+    file_name: String,
+    crc: u32,
+}
+
+fn debug_link(elf: &Elf, bytes: &[u8]) -> Option<DebugLink> {
+    let range = StackMap::get_section_byte_range(elf, ".gnu_debuglink")?;
+    let data = bytes.get(range)?;
+    let name_len = data.iter().position(|b| *b == 0)?;
+    let file_name = std::str::from_utf8(&data[..name_len]).ok()?.to_owned();
+    // The name is followed by padding to the next 4 byte boundary and the CRC.
+    let crc_offset = (name_len + 1 + 3) & !3;
+    let crc: [u8; 4] = data.get(crc_offset..crc_offset + 4)?.try_into().ok()?;
+    let crc = if elf.little_endian {
+        u32::from_le_bytes(crc)
+    } else {
+        u32::from_be_bytes(crc)
+    };
+    Some(DebugLink { file_name, crc })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl DebugFileLocator {
+    /// Create a locator that searches the given global debug directories.
+    pub fn new(debug_dirs: Vec<PathBuf>) -> DebugFileLocator {
+        DebugFileLocator { debug_dirs }
+    }
+
+    /// Add `dir` to the searched global debug directories.
+    pub fn with_debug_dir<T: Into<PathBuf>>(mut self, dir: T) -> DebugFileLocator {
+        self.debug_dirs.push(dir.into());
+        self
+    }
+
+    /// Get the build ID of `elf`, if it has one. `elf` must be parsed from `bytes`.
+    pub fn build_id(elf: &Elf, bytes: &[u8]) -> Option<Vec<u8>> {
+        elf.iter_note_sections(bytes, None)?
+            .filter_map(|note| note.ok())
+            .find(|note| note.n_type == NT_GNU_BUILD_ID && note.name == "GNU")
+            .map(|note| note.desc.to_vec())
+    }
+
+    /// Find the debug file of the binary at `path`. `elf` must be parsed from `bytes`,
+    /// the content of `path`. Candidates found via the build ID are only returned if
+    /// their build ID matches, those found via `.gnu_debuglink` if their CRC matches.
+    pub fn find(&self, path: &Path, elf: &Elf, bytes: &[u8]) -> Option<PathBuf> {
+        if let Some(build_id) = DebugFileLocator::build_id(elf, bytes).filter(|id| id.len() >= 2) {
+            let dir = hex(&build_id[..1]);
+            let file = format!("{}.debug", hex(&build_id[1..]));
+            for debug_dir in self.debug_dirs.iter() {
+                let candidate = debug_dir.join(".build-id").join(&dir).join(&file);
+                if DebugFileLocator::has_build_id(&candidate, &build_id) {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        let link = debug_link(elf, bytes)?;
+        let binary_dir = fs::canonicalize(path)
+            .ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let mut candidates = vec![
+            binary_dir.join(&link.file_name),
+            binary_dir.join(".debug").join(&link.file_name),
+        ];
+        for debug_dir in self.debug_dirs.iter() {
+            let relative_dir = binary_dir.strip_prefix("/").unwrap_or(&binary_dir);
+            candidates.push(debug_dir.join(relative_dir).join(&link.file_name));
+        }
+
+        let own_path = fs::canonicalize(path).ok();
+        candidates.into_iter().find(|candidate| {
+            // The debug link might name the binary itself.
+            fs::canonicalize(candidate).ok() != own_path
+                && fs::read(candidate).map_or(false, |data| crc32fast::hash(&data) == link.crc)
+        })
+    }
+
+    /// Same as `find`, but read and parse the binary at `path` first.
+    pub fn find_for_path<T: AsRef<Path>>(&self, path: T) -> Result<Option<PathBuf>, ParsingError> {
+        let bytes = fs::read(path.as_ref())?;
+        let elf = Elf::parse(&bytes)?;
+        Ok(self.find(path.as_ref(), &elf, &bytes))
+    }
+
+    fn has_build_id(candidate: &Path, build_id: &[u8]) -> bool {
+        let bytes = match fs::read(candidate) {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+        Elf::parse(&bytes)
+            .ok()
+            .and_then(|elf| DebugFileLocator::build_id(&elf, &bytes))
+            .map_or(false, |id| id == build_id)
+    }
+}
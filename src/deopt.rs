@@ -0,0 +1,161 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    EvaluationError, IndexedRecord, MemoryProvider, RegisterProvider, StkMapRecord, Value,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeoptError {
+    /// Evaluating the location with the given index failed.
+    Evaluation(usize, EvaluationError),
+    /// The slot with the given name refers to a location index the record does
+    /// not have.
+    MissingLocation { slot: String, location_idx: usize },
+}
+
+impl fmt::Display for DeoptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeoptError::Evaluation(idx, err) => write!(f, "location #{}: {}", idx + 1, err),
+            DeoptError::MissingLocation { slot, location_idx } => write!(
+                f,
+                "slot {} refers to missing location #{}",
+                slot,
+                location_idx + 1
+            ),
+        }
+    }
+}
+
+impl StkMapRecord {
+    /// Evaluate all locations of this record (see `Location::evaluate_value`).
+    /// `constants` must be the large constants of the stackmap the record
+    /// belongs to.
+    pub fn evaluate_locations(
+        &self,
+        constants: &[u64],
+        regs: &impl RegisterProvider,
+        mem: &impl MemoryProvider,
+    ) -> Result<Vec<Value>, DeoptError> {
+        self.locations()
+            .iter()
+            .enumerate()
+            .map(|(idx, loc)| {
+                loc.evaluate_value(constants, regs, mem)
+                    .map_err(|err| DeoptError::Evaluation(idx, err))
+            })
+            .collect()
+    }
+}
+
+impl IndexedRecord<'_> {
+    /// Evaluate all locations of the record (see `StkMapRecord::evaluate_locations`).
+    pub fn evaluate_locations(
+        &self,
+        regs: &impl RegisterProvider,
+        mem: &impl MemoryProvider,
+    ) -> Result<Vec<Value>, DeoptError> {
+        self.record.evaluate_locations(self.constants(), regs, mem)
+    }
+}
+
+/// A named slot of the interpreter state, e.g., a local variable or the
+/// bytecode index, and the index of the location that holds its value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeoptSlot {
+    pub name: String,
+    pub location_idx: usize,
+}
+
+/// Describes which locations of a record hold which parts of the state required
+/// to deoptimize, e.g., to resume execution in an interpreter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeoptSchema {
+    slots: Vec<DeoptSlot>,
+}
+
+impl DeoptSchema {
+    /// Create a schema without any slots.
+    pub fn new() -> DeoptSchema {
+        DeoptSchema::default()
+    }
+
+    /// Add a slot called `name` whose value is held by the location with the
+    /// index `location_idx`.
+    pub fn with_slot<T: Into<String>>(mut self, name: T, location_idx: usize) -> DeoptSchema {
+        self.slots.push(DeoptSlot {
+            name: name.into(),
+            location_idx,
+        });
+        self
+    }
+
+    /// Create a schema that maps the locations starting at `first_location_idx`
+    /// to the slots `names` in order, e.g., the deopt locations of a statepoint.
+    pub fn from_names<T: Into<String>>(
+        first_location_idx: usize,
+        names: impl IntoIterator<Item = T>,
+    ) -> DeoptSchema {
+        names
+            .into_iter()
+            .enumerate()
+            .fold(DeoptSchema::new(), |schema, (idx, name)| {
+                schema.with_slot(name, first_location_idx + idx)
+            })
+    }
+
+    pub fn slots(&self) -> &[DeoptSlot] {
+        &self.slots
+    }
+
+    /// Evaluate the locations of `record` and assign them to the slots of this
+    /// schema. Only the locations referred to by slots are evaluated.
+    pub fn extract(
+        &self,
+        record: &IndexedRecord,
+        regs: &impl RegisterProvider,
+        mem: &impl MemoryProvider,
+    ) -> Result<DeoptState, DeoptError> {
+        let locations = record.record.locations();
+        let values = self
+            .slots
+            .iter()
+            .map(|slot| {
+                let loc = locations.get(slot.location_idx).ok_or_else(|| {
+                    DeoptError::MissingLocation {
+                        slot: slot.name.clone(),
+                        location_idx: slot.location_idx,
+                    }
+                })?;
+                let value = loc
+                    .evaluate_value(record.constants(), regs, mem)
+                    .map_err(|err| DeoptError::Evaluation(slot.location_idx, err))?;
+                Ok((slot.name.clone(), value))
+            })
+            .collect::<Result<_, DeoptError>>()?;
+        Ok(DeoptState { values })
+    }
+}
+
+/// The values of the slots of a `DeoptSchema` at a specific record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeoptState {
+    /// (slot name, value) pairs in the order of the schema.
+    pub values: Vec<(String, Value)>,
+}
+
+impl DeoptState {
+    /// Get the value of the slot called `name`.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.values
+            .iter()
+            .find(|(slot, _)| slot == name)
+            .map(|(_, value)| *value)
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{LiveOut, Location, LocationType, StackMap, StkMapRecord};
+
+/// A single difference between the stackmaps of two builds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StackMapChange {
+    /// The patch point only exists in the new build.
+    Added {
+        patch_point_id: u64,
+        function: String,
+        instruction_offset: u32,
+    },
+    /// The patch point only exists in the old build.
+    Removed {
+        patch_point_id: u64,
+        function: String,
+        instruction_offset: u32,
+    },
+    /// The patch point belongs to a different function or is located at another offset.
+    Moved {
+        patch_point_id: u64,
+        old_function: String,
+        old_instruction_offset: u32,
+        new_function: String,
+        new_instruction_offset: u32,
+    },
+    /// The kind or size of a location changed. A location that only exists in
+    /// one of the builds is represented by None.
+    LocationChanged {
+        patch_point_id: u64,
+        location_idx: usize,
+        old: Option<(LocationType, u16)>,
+        new: Option<(LocationType, u16)>,
+    },
+    /// The set of live out registers changed.
+    LiveOutsChanged {
+        patch_point_id: u64,
+        old: Vec<LiveOut>,
+        new: Vec<LiveOut>,
+    },
+    /// The stack size of a function that exists in both builds changed.
+    StackSizeChanged {
+        function: String,
+        old: u64,
+        new: u64,
+    },
+}
+
+impl fmt::Display for StackMapChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn fmt_location(loc: &Option<(LocationType, u16)>) -> String {
+            match loc {
+                Some((loc_type, size)) => format!("{:?} (size {})", loc_type, size),
+                None => "<none>".to_owned(),
+            }
+        }
+        fn fmt_live_outs(live_outs: &[LiveOut]) -> String {
+            let live_outs = live_outs
+                .iter()
+                .map(|lo| lo.to_string())
+                .collect::<Vec<_>>();
+            format!("[{}]", live_outs.join(", "))
+        }
+
+        match self {
+            StackMapChange::Added {
+                patch_point_id,
+                function,
+                instruction_offset,
+            } => write!(
+                f,
+                "+ patch point {} in {} at offset {}",
+                patch_point_id, function, instruction_offset
+            ),
+            StackMapChange::Removed {
+                patch_point_id,
+                function,
+                instruction_offset,
+            } => write!(
+                f,
+                "- patch point {} in {} at offset {}",
+                patch_point_id, function, instruction_offset
+            ),
+            StackMapChange::Moved {
+                patch_point_id,
+                old_function,
+                old_instruction_offset,
+                new_function,
+                new_instruction_offset,
+            } => write!(
+                f,
+                "~ patch point {} moved from {}+{} to {}+{}",
+                patch_point_id,
+                old_function,
+                old_instruction_offset,
+                new_function,
+                new_instruction_offset
+            ),
+            StackMapChange::LocationChanged {
+                patch_point_id,
+                location_idx,
+                old,
+                new,
+            } => write!(
+                f,
+                "~ patch point {} location #{}: {} -> {}",
+                patch_point_id,
+                location_idx + 1,
+                fmt_location(old),
+                fmt_location(new)
+            ),
+            StackMapChange::LiveOutsChanged {
+                patch_point_id,
+                old,
+                new,
+            } => write!(
+                f,
+                "~ patch point {} live-outs: {} -> {}",
+                patch_point_id,
+                fmt_live_outs(old),
+                fmt_live_outs(new)
+            ),
+            StackMapChange::StackSizeChanged { function, old, new } => {
+                write!(f, "~ function {} stack size: {} -> {}", function, old, new)
+            }
+        }
+    }
+}
+
+/// A record together with the function it belongs to.
+struct PatchPoint<'a> {
+    function: String,
+    record: &'a StkMapRecord,
+}
+
+/// Identifies a patch point by its function, its patch point ID and the number of
+/// preceding records of the function with the same ID.
+type PatchPointKey = (String, u64, usize);
+
+/// Collect all patch points of `maps`. Patch points sharing the same ID within a
+/// function are distinguished by the order of their occurrence.
+fn collect_patch_points<'a>(
+    maps: &'a [StackMap],
+    function_key: &impl Fn(u64) -> String,
+) -> (
+    BTreeMap<PatchPointKey, PatchPoint<'a>>,
+    HashMap<String, u64>,
+) {
+    let mut stack_sizes = HashMap::new();
+    let mut patch_points = BTreeMap::new();
+    let mut occurrences: HashMap<(String, u64), usize> = HashMap::new();
+    for (function, records) in maps.iter().flat_map(|map| map.function_records()) {
+        let function_key = function_key(function.function_address);
+        stack_sizes.insert(function_key.clone(), function.stack_size);
+        for record in records {
+            let nth = occurrences
+                .entry((function_key.clone(), record.patch_point_id))
+                .or_default();
+            let key = (function_key.clone(), record.patch_point_id, *nth);
+            *nth += 1;
+            let function = function_key.clone();
+            patch_points.insert(key, PatchPoint { function, record });
+        }
+    }
+    (patch_points, stack_sizes)
+}
+
+/// Pair the patch points of `old` and `new`. Patch points with the same key are
+/// paired first. The remaining ones are paired by their patch point ID in the
+/// order of their occurrence, i.e., they moved to another function. Returns the
+/// key of the new patch point paired with each old one.
+fn pair_patch_points<'k>(
+    old: &'k BTreeMap<PatchPointKey, PatchPoint<'_>>,
+    new: &'k BTreeMap<PatchPointKey, PatchPoint<'_>>,
+) -> HashMap<&'k PatchPointKey, &'k PatchPointKey> {
+    let mut pairs: HashMap<&PatchPointKey, &PatchPointKey> = old
+        .keys()
+        .filter(|key| new.contains_key(*key))
+        .map(|key| (key, key))
+        .collect();
+
+    let mut unpaired_new: HashMap<u64, Vec<&PatchPointKey>> = HashMap::new();
+    for key in new.keys().filter(|key| !old.contains_key(*key)).rev() {
+        unpaired_new.entry(key.1).or_default().push(key);
+    }
+    for key in old.keys().filter(|key| !new.contains_key(*key)) {
+        if let Some(new_key) = unpaired_new.get_mut(&key.1).and_then(|keys| keys.pop()) {
+            pairs.insert(key, new_key);
+        }
+    }
+    pairs
+}
+
+fn patch_point_ids(records: &[StkMapRecord]) -> HashSet<u64> {
+    records.iter().map(|record| record.patch_point_id).collect()
+}
+
+/// Map the addresses of all symbolized functions of `maps` to their name.
+fn function_names(maps: &[StackMap]) -> HashMap<u64, String> {
+    maps.iter()
+        .flat_map(|map| map.symbols().iter())
+        .map(|symbol| (symbol.address, symbol.name.clone()))
+        .collect()
+}
+
+fn name_or_address(names: &HashMap<u64, String>, address: u64) -> String {
+    names
+        .get(&address)
+        .cloned()
+        .unwrap_or_else(|| format!("{:#x}", address))
+}
+
+/// Kind and size of a location, i.e., the properties compared by the diff.
+fn location_shape(loc: &Location) -> (LocationType, u16) {
+    (loc.loc_type, loc.loc_size)
+}
+
+/// Get the live outs of `record` in a canonical order.
+fn sorted_live_outs(record: &StkMapRecord) -> Vec<LiveOut> {
+    let mut live_outs = record.live_outs.clone();
+    live_outs.sort_by_key(|lo| (lo.dwarf_regnum(), lo.size()));
+    live_outs
+}
+
+impl StackMap {
+    /// Compare the stackmaps `old` and `new` of two builds. Patch points are
+    /// identified by their function, their `patch_point_id` and, if an ID is used
+    /// multiple times within a function, the order of their occurrence. Patch points
+    /// that only exist in different functions of both builds are reported as moved
+    /// if they share their ID (e.g., the default ID of all `gc.statepoint`s).
+    ///
+    /// Functions are identified by their symbol name if the stackmaps are
+    /// symbolized. Otherwise, a function of `new` is identified with the next
+    /// function of `old` that shares a patch point ID with it, and both are named
+    /// after the address of the latter (see `diff_with` to use another identity).
+    pub fn diff(old: &[StackMap], new: &[StackMap]) -> Vec<StackMapChange> {
+        let old_names = function_names(old);
+        let mut new_names = function_names(new);
+
+        // Since the addresses of functions change between builds, an unsymbolized
+        // function of `new` is identified with an unsymbolized function of `old`
+        // via the patch points both contain. Functions keep their order between
+        // builds, so the search continues after the previously identified function.
+        let old_functions: Vec<(u64, HashSet<u64>)> = old
+            .iter()
+            .flat_map(|map| map.function_records())
+            .filter(|(function, _)| !old_names.contains_key(&function.function_address))
+            .map(|(function, records)| (function.function_address, patch_point_ids(records)))
+            .collect();
+        let mut next_old = 0;
+        for (function, records) in new.iter().flat_map(|map| map.function_records()) {
+            if new_names.contains_key(&function.function_address) {
+                continue;
+            }
+            let ids = patch_point_ids(records);
+            let matching = old_functions[next_old..]
+                .iter()
+                .position(|(_, old_ids)| !ids.is_disjoint(old_ids));
+            if let Some(idx) = matching {
+                let old_address = old_functions[next_old + idx].0;
+                new_names.insert(function.function_address, format!("{:#x}", old_address));
+                next_old += idx + 1;
+            }
+        }
+
+        StackMap::diff_with(
+            old,
+            new,
+            |addr| name_or_address(&old_names, addr),
+            |addr| name_or_address(&new_names, addr),
+        )
+    }
+
+    /// Same as `diff`, but functions are identified by the keys returned by
+    /// `old_function_key` and `new_function_key` for the function addresses of
+    /// `old` and `new`, respectively.
+    ///
+    /// Locations are only compared by their kind and size, since changes of the
+    /// used registers or stack offsets are expected between builds.
+    pub fn diff_with(
+        old: &[StackMap],
+        new: &[StackMap],
+        old_function_key: impl Fn(u64) -> String,
+        new_function_key: impl Fn(u64) -> String,
+    ) -> Vec<StackMapChange> {
+        let (old_points, old_stack_sizes) = collect_patch_points(old, &old_function_key);
+        let (new_points, new_stack_sizes) = collect_patch_points(new, &new_function_key);
+        let mut changes = Vec::new();
+
+        let mut functions = old_stack_sizes.iter().collect::<Vec<_>>();
+        functions.sort();
+        for (function, old_size) in functions {
+            match new_stack_sizes.get(function) {
+                Some(new_size) if new_size != old_size => {
+                    changes.push(StackMapChange::StackSizeChanged {
+                        function: function.clone(),
+                        old: *old_size,
+                        new: *new_size,
+                    })
+                }
+                _ => (),
+            }
+        }
+
+        let pairs = pair_patch_points(&old_points, &new_points);
+        for (key, old_point) in old_points.iter() {
+            let patch_point_id = key.1;
+            let old_record = old_point.record;
+            let new_point = match pairs.get(key) {
+                Some(new_key) => &new_points[*new_key],
+                None => {
+                    changes.push(StackMapChange::Removed {
+                        patch_point_id,
+                        function: old_point.function.clone(),
+                        instruction_offset: old_record.instruction_offset,
+                    });
+                    continue;
+                }
+            };
+            let new_record = new_point.record;
+
+            if old_point.function != new_point.function
+                || old_record.instruction_offset != new_record.instruction_offset
+            {
+                changes.push(StackMapChange::Moved {
+                    patch_point_id,
+                    old_function: old_point.function.clone(),
+                    old_instruction_offset: old_record.instruction_offset,
+                    new_function: new_point.function.clone(),
+                    new_instruction_offset: new_record.instruction_offset,
+                });
+            }
+
+            let num_locations = old_record.locations.len().max(new_record.locations.len());
+            for location_idx in 0..num_locations {
+                let old = old_record.locations.get(location_idx).map(location_shape);
+                let new = new_record.locations.get(location_idx).map(location_shape);
+                if old != new {
+                    changes.push(StackMapChange::LocationChanged {
+                        patch_point_id,
+                        location_idx,
+                        old,
+                        new,
+                    });
+                }
+            }
+
+            let old_live_outs = sorted_live_outs(old_record);
+            let new_live_outs = sorted_live_outs(new_record);
+            if old_live_outs != new_live_outs {
+                changes.push(StackMapChange::LiveOutsChanged {
+                    patch_point_id,
+                    old: old_live_outs,
+                    new: new_live_outs,
+                });
+            }
+        }
+
+        let paired_new = pairs.values().collect::<HashSet<_>>();
+        for (key, new_point) in new_points.iter() {
+            if !paired_new.contains(&key) {
+                changes.push(StackMapChange::Added {
+                    patch_point_id: key.1,
+                    function: new_point.function.clone(),
+                    instruction_offset: new_point.record.instruction_offset,
+                });
+            }
+        }
+
+        changes
+    }
+}
@@ -0,0 +1,300 @@
+use std::{convert::TryFrom, fmt};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{LiveOut, Location, LocationType};
+
+/// Provides the register values of a stopped thread.
+pub trait RegisterProvider {
+    /// Get the value of the register with the DWARF register number `dwarf_regnum`,
+    /// or None if the value is not available.
+    fn read_register(&self, dwarf_regnum: u16) -> Option<u64>;
+
+    /// Fill `buf` with the lower `buf.len()` bytes of the register with the DWARF
+    /// register number `dwarf_regnum` in little endian order. Returns false if the
+    /// value is not available.
+    ///
+    /// This must be implemented to read registers wider than 8 bytes, e.g. vector
+    /// registers. By default, it is based on `read_register`.
+    fn read_register_bytes(&self, dwarf_regnum: u16, buf: &mut [u8]) -> bool {
+        match self.read_register(dwarf_regnum) {
+            Some(value) if buf.len() <= 8 => {
+                buf.copy_from_slice(&value.to_le_bytes()[..buf.len()]);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<F> RegisterProvider for F
+where
+    F: Fn(u16) -> Option<u64>,
+{
+    fn read_register(&self, dwarf_regnum: u16) -> Option<u64> {
+        self(dwarf_regnum)
+    }
+}
+
+/// Provides read access to the memory of a stopped thread.
+pub trait MemoryProvider {
+    /// Fill `buf` with the bytes located at `address`. Returns false if the memory
+    /// could not be read.
+    fn read_memory(&self, address: u64, buf: &mut [u8]) -> bool;
+}
+
+impl<F> MemoryProvider for F
+where
+    F: Fn(u64, &mut [u8]) -> bool,
+{
+    fn read_memory(&self, address: u64, buf: &mut [u8]) -> bool {
+        self(address, buf)
+    }
+}
+
+/// A value described by a location, typed according to its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Value {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    /// A 16 byte value, e.g. held in a vector register.
+    U128(u128),
+    /// A 32 byte value held in a vector register (e.g., ymm0), in little endian
+    /// byte order.
+    U256([u8; 32]),
+    /// The address of a value, as described by `Direct` locations.
+    Address(u64),
+}
+
+impl Value {
+    /// Create a value from its little endian representation of 1, 2, 4, 8, 16 or
+    /// 32 bytes.
+    fn from_le_bytes(bytes: &[u8]) -> Result<Value, EvaluationError> {
+        if let Ok(value) = <[u8; 32]>::try_from(bytes) {
+            return Ok(Value::U256(value));
+        }
+        let mut buf = [0u8; 16];
+        match buf.get_mut(..bytes.len()) {
+            Some(buf) => buf.copy_from_slice(bytes),
+            None => return Err(EvaluationError::UnsupportedSize(bytes.len() as u16)),
+        }
+        let value = u128::from_le_bytes(buf);
+        match bytes.len() {
+            1 => Ok(Value::U8(value as u8)),
+            2 => Ok(Value::U16(value as u16)),
+            4 => Ok(Value::U32(value as u32)),
+            8 => Ok(Value::U64(value as u64)),
+            16 => Ok(Value::U128(value)),
+            size => Err(EvaluationError::UnsupportedSize(size as u16)),
+        }
+    }
+
+    /// The value zero extended to 64 bit, or None if it does not fit.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::U8(value) => Some(value.into()),
+            Value::U16(value) => Some(value.into()),
+            Value::U32(value) => Some(value.into()),
+            Value::U64(value) | Value::Address(value) => Some(value),
+            Value::U128(value) => u64::try_from(value).ok(),
+            Value::U256(bytes) => match bytes[8..].iter().all(|b| *b == 0) {
+                true => Some(u64::from_le_bytes(bytes[..8].try_into().unwrap())),
+                false => None,
+            },
+        }
+    }
+
+    /// The size of the value in bytes.
+    pub fn size(&self) -> u16 {
+        match self {
+            Value::U8(_) => 1,
+            Value::U16(_) => 2,
+            Value::U32(_) => 4,
+            Value::U64(_) | Value::Address(_) => 8,
+            Value::U128(_) => 16,
+            Value::U256(_) => 32,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::U8(value) => write!(f, "{:#x}", value),
+            Value::U16(value) => write!(f, "{:#x}", value),
+            Value::U32(value) => write!(f, "{:#x}", value),
+            Value::U64(value) => write!(f, "{:#x}", value),
+            Value::U128(value) => write!(f, "{:#x}", value),
+            Value::U256(bytes) => {
+                let high = u128::from_le_bytes(bytes[16..].try_into().unwrap());
+                let low = u128::from_le_bytes(bytes[..16].try_into().unwrap());
+                match high {
+                    0 => write!(f, "{:#x}", low),
+                    _ => write!(f, "{:#x}{:032x}", high, low),
+                }
+            }
+            Value::Address(address) => write!(f, "&{:#x}", address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvaluationError {
+    /// The value of the register with the given DWARF register number is not available.
+    RegisterUnavailable(u16),
+    /// The memory at the given address could not be read.
+    MemoryUnreadable(u64),
+    /// The location has the type `LocationType::Invalid`.
+    InvalidLocation,
+    /// A ConstIndex location refers to a constant that does not exist.
+    ConstIndexOutOfBounds(i32),
+    /// Values of the given size can not be represented.
+    UnsupportedSize(u16),
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluationError::RegisterUnavailable(regnum) => {
+                write!(f, "register R#{} is not available", regnum)
+            }
+            EvaluationError::MemoryUnreadable(address) => {
+                write!(f, "failed to read memory at {:#x}", address)
+            }
+            EvaluationError::InvalidLocation => write!(f, "invalid location"),
+            EvaluationError::ConstIndexOutOfBounds(idx) => {
+                write!(f, "constant index {} is out of range", idx)
+            }
+            EvaluationError::UnsupportedSize(size) => {
+                write!(f, "values of size {} are not supported", size)
+            }
+        }
+    }
+}
+
+/// Truncate `value` to its lower `size` bytes.
+fn truncate(value: u64, size: u16) -> Result<u64, EvaluationError> {
+    match size {
+        1 | 2 | 4 => Ok(value & ((1u64 << (size * 8)) - 1)),
+        8 => Ok(value),
+        _ => Err(EvaluationError::UnsupportedSize(size)),
+    }
+}
+
+fn read_register(regs: &impl RegisterProvider, dwarf_regnum: u16) -> Result<u64, EvaluationError> {
+    regs.read_register(dwarf_regnum)
+        .ok_or(EvaluationError::RegisterUnavailable(dwarf_regnum))
+}
+
+impl Location {
+    /// Get the value described by this location. `constants` must be the large
+    /// constants of the stackmap the location belongs to.
+    ///
+    /// For Direct locations, the address of the value is returned, since this is
+    /// what LLVM records for e.g. allocas.
+    pub fn evaluate(
+        &self,
+        constants: &[u64],
+        regs: &impl RegisterProvider,
+        mem: &impl MemoryProvider,
+    ) -> Result<u64, EvaluationError> {
+        match self.loc_type {
+            LocationType::Invalid => Err(EvaluationError::InvalidLocation),
+            LocationType::Register => {
+                truncate(read_register(regs, self.dwarf_regnum)?, self.loc_size)
+            }
+            LocationType::Direct => Ok(read_register(regs, self.dwarf_regnum)?
+                .wrapping_add(self.offset_or_constant as i64 as u64)),
+            LocationType::Indirect => {
+                let address = read_register(regs, self.dwarf_regnum)?
+                    .wrapping_add(self.offset_or_constant as i64 as u64);
+                let mut buf = [0u8; 8];
+                let size = self.loc_size as usize;
+                if size > buf.len() {
+                    return Err(EvaluationError::UnsupportedSize(self.loc_size));
+                }
+                if !mem.read_memory(address, &mut buf[..size]) {
+                    return Err(EvaluationError::MemoryUnreadable(address));
+                }
+                truncate(u64::from_le_bytes(buf), self.loc_size)
+            }
+            // Small constants are sign extended to 64 bit.
+            LocationType::Constant => Ok(self.offset_or_constant as i64 as u64),
+            LocationType::ConstIndex => usize::try_from(self.offset_or_constant)
+                .ok()
+                .and_then(|idx| constants.get(idx))
+                .copied()
+                .ok_or(EvaluationError::ConstIndexOutOfBounds(
+                    self.offset_or_constant,
+                )),
+        }
+    }
+
+    /// Get the value described by this location, typed according to its
+    /// `loc_size`. In contrast to `evaluate`, this also supports values of 16 and
+    /// 32 bytes, e.g. held in vector registers (see
+    /// `RegisterProvider::read_register_bytes`) or spilled from them.
+    pub fn evaluate_value(
+        &self,
+        constants: &[u64],
+        regs: &impl RegisterProvider,
+        mem: &impl MemoryProvider,
+    ) -> Result<Value, EvaluationError> {
+        let size = self.loc_size as usize;
+        let mut buf = [0u8; 32];
+        if size > buf.len() {
+            return Err(EvaluationError::UnsupportedSize(self.loc_size));
+        }
+        match self.loc_type {
+            LocationType::Register => {
+                if !regs.read_register_bytes(self.dwarf_regnum, &mut buf[..size]) {
+                    return Err(EvaluationError::RegisterUnavailable(self.dwarf_regnum));
+                }
+                Value::from_le_bytes(&buf[..size])
+            }
+            LocationType::Indirect => {
+                let address = read_register(regs, self.dwarf_regnum)?
+                    .wrapping_add(self.offset_or_constant as i64 as u64);
+                if !mem.read_memory(address, &mut buf[..size]) {
+                    return Err(EvaluationError::MemoryUnreadable(address));
+                }
+                Value::from_le_bytes(&buf[..size])
+            }
+            LocationType::Direct => Ok(Value::Address(self.evaluate(constants, regs, mem)?)),
+            LocationType::Constant | LocationType::ConstIndex => {
+                let value = self.evaluate(constants, regs, mem)?;
+                buf[..8].copy_from_slice(&value.to_le_bytes());
+                // Constants are recorded with a size of 8 bytes by LLVM.
+                Value::from_le_bytes(&buf[..size.min(8)])
+            }
+            LocationType::Invalid => Err(EvaluationError::InvalidLocation),
+        }
+    }
+}
+
+impl LiveOut {
+    /// Get the current value of the register that is live out, using the same
+    /// register provider as `Location::evaluate`.
+    pub fn evaluate(&self, regs: &impl RegisterProvider) -> Result<u64, EvaluationError> {
+        truncate(read_register(regs, self.dwarf_regnum)?, self.size as u16)
+    }
+
+    /// Get the current value of the register that is live out, typed according
+    /// to its size. In contrast to `evaluate`, this also supports vector registers
+    /// (see `RegisterProvider::read_register_bytes`).
+    pub fn evaluate_value(&self, regs: &impl RegisterProvider) -> Result<Value, EvaluationError> {
+        let size = self.size as usize;
+        let mut buf = [0u8; 32];
+        if size > buf.len() {
+            return Err(EvaluationError::UnsupportedSize(self.size.into()));
+        }
+        if !regs.read_register_bytes(self.dwarf_regnum, &mut buf[..size]) {
+            return Err(EvaluationError::RegisterUnavailable(self.dwarf_regnum));
+        }
+        Value::from_le_bytes(&buf[..size])
+    }
+}
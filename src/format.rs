@@ -0,0 +1,97 @@
+use std::io::Read;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{ParseOptions, ParsingError, StackMap};
+
+/// The container format of a file that contains a stackmap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BinaryFormat {
+    Elf,
+    /// A single-architecture or universal (fat) Mach-O binary.
+    MachO,
+    /// A PE image or COFF object.
+    Pe,
+    /// A static `ar` archive of object files.
+    Archive,
+    /// The content of a stackmap section without any container.
+    Raw,
+}
+
+const MACHO_MAGICS: [[u8; 4]; 6] = [
+    [0xfe, 0xed, 0xfa, 0xce],
+    [0xfe, 0xed, 0xfa, 0xcf],
+    [0xce, 0xfa, 0xed, 0xfe],
+    [0xcf, 0xfa, 0xed, 0xfe],
+    // Universal binaries
+    [0xca, 0xfe, 0xba, 0xbe],
+    [0xca, 0xfe, 0xba, 0xbf],
+];
+
+/// The `Machine` field of the COFF objects we support (x86_64 and AArch64).
+const COFF_MACHINES: [[u8; 2]; 2] = [[0x64, 0x86], [0x64, 0xaa]];
+
+impl BinaryFormat {
+    /// Determine the format of `bytes` based on its magic number. Data that is not
+    /// recognized as one of the container formats is assumed to be a raw stackmap.
+    pub fn detect(bytes: &[u8]) -> BinaryFormat {
+        if bytes.starts_with(b"\x7fELF") {
+            BinaryFormat::Elf
+        } else if crate::archive::is_archive(bytes) {
+            BinaryFormat::Archive
+        } else if MACHO_MAGICS.iter().any(|magic| bytes.starts_with(magic)) {
+            BinaryFormat::MachO
+        } else if bytes.starts_with(b"MZ") || COFF_MACHINES.iter().any(|m| bytes.starts_with(m)) {
+            BinaryFormat::Pe
+        } else {
+            BinaryFormat::Raw
+        }
+    }
+}
+
+impl StackMap {
+    /// Parse the stackmap(s) contained in `bytes`, which can either be an ELF,
+    /// Mach-O or PE/COFF file, an archive of such files, or the raw content of a
+    /// stackmap section (see `BinaryFormat::detect`). For archives, the stackmaps
+    /// of all members are returned. Support for the container formats requires the
+    /// corresponding `from-*` feature.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Vec<StackMap>, ParsingError> {
+        StackMap::from_bytes_with_options(bytes, &Default::default())
+    }
+
+    /// Same as `from_bytes`, but the stackmap section is searched as configured
+    /// by `options`.
+    #[allow(unused_variables)]
+    pub fn from_bytes_with_options(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        match BinaryFormat::detect(bytes) {
+            #[cfg(feature = "from-elf")]
+            BinaryFormat::Elf => StackMap::from_elf_bytes(bytes, options),
+            #[cfg(feature = "from-macho")]
+            BinaryFormat::MachO => StackMap::from_macho_bytes(bytes, options),
+            #[cfg(feature = "from-pe")]
+            BinaryFormat::Pe => StackMap::from_pe_bytes(bytes, options),
+            BinaryFormat::Archive => {
+                let members = StackMap::from_archive_bytes(bytes, options)?;
+                Ok(members.into_iter().flat_map(|(_, maps)| maps).collect())
+            }
+            BinaryFormat::Raw => StackMap::new(&mut bytes.to_vec()),
+            #[allow(unreachable_patterns)]
+            format => Err(ParsingError::Malformed(format!(
+                "Support for {:?} files is not enabled",
+                format
+            ))),
+        }
+    }
+
+    /// Read all data from `reader` and parse it using `from_bytes`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Vec<StackMap>, ParsingError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        StackMap::from_bytes(&bytes)
+    }
+}
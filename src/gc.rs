@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::{
+    EvaluationError, Location, LocationType, RegisterProvider, StackMapIndex, StatepointError,
+    StatepointRecord,
+};
+
+/// The stack slots of a GC pointer that is live at a statepoint. A moving collector
+/// updates the pointers stored in these slots in place after moving the object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GcRoot {
+    /// Address of the slot that holds the pointer to the start of the object.
+    pub base_slot: u64,
+    /// Address of the slot that holds the (possibly interior) pointer that is
+    /// actually used. Equal to `base_slot` if the pointer is not derived.
+    pub derived_slot: u64,
+}
+
+impl GcRoot {
+    /// Whether the pointer in `derived_slot` might point into the object instead
+    /// of to its start.
+    pub fn is_derived(&self) -> bool {
+        self.base_slot != self.derived_slot
+    }
+
+    /// Get the new value of a derived pointer after its object moved from
+    /// `old_base` to `new_base`. The offset of the derived pointer relative to
+    /// its base is preserved.
+    pub fn relocate_derived(old_base: u64, old_derived: u64, new_base: u64) -> u64 {
+        new_base.wrapping_add(old_derived.wrapping_sub(old_base))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GcRootError {
+    /// There is no record for the given return address.
+    NoRecord(u64),
+    /// The record for the return address is not a statepoint record.
+    NotAStatepoint(StatepointError),
+    /// The GC pointer described by the location with the given index is not
+    /// stored on the stack (e.g., it is held in a register) and can not be
+    /// updated in place.
+    NotInMemory(usize),
+    /// The address of a slot could not be determined.
+    Evaluation(EvaluationError),
+}
+
+impl fmt::Display for GcRootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GcRootError::NoRecord(address) => {
+                write!(f, "no record for return address {:#x}", address)
+            }
+            GcRootError::NotAStatepoint(err) => write!(f, "not a statepoint record: {}", err),
+            GcRootError::NotInMemory(idx) => {
+                write!(
+                    f,
+                    "GC pointer of location #{} is not stored in memory",
+                    idx + 1
+                )
+            }
+            GcRootError::Evaluation(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<EvaluationError> for GcRootError {
+    fn from(err: EvaluationError) -> Self {
+        GcRootError::Evaluation(err)
+    }
+}
+
+impl From<StatepointError> for GcRootError {
+    fn from(err: StatepointError) -> Self {
+        GcRootError::NotAStatepoint(err)
+    }
+}
+
+/// Get the address of the slot that holds the GC pointer of `loc`, or None if the
+/// pointer is a constant (e.g., null) that does not need to be updated.
+fn slot_address(
+    loc: &Location,
+    location_idx: usize,
+    regs: &impl RegisterProvider,
+) -> Result<Option<u64>, GcRootError> {
+    match loc.loc_type {
+        LocationType::Indirect => {
+            let reg = regs
+                .read_register(loc.dwarf_regnum)
+                .ok_or(EvaluationError::RegisterUnavailable(loc.dwarf_regnum))?;
+            Ok(Some(reg.wrapping_add(loc.offset_or_constant as i64 as u64)))
+        }
+        LocationType::Constant | LocationType::ConstIndex => Ok(None),
+        LocationType::Invalid => Err(EvaluationError::InvalidLocation.into()),
+        LocationType::Register | LocationType::Direct => {
+            Err(GcRootError::NotInMemory(location_idx))
+        }
+    }
+}
+
+impl StatepointRecord<'_> {
+    /// Get the stack slots of all GC pointers that are live at this statepoint.
+    /// `regs` must provide the register values of the frame the statepoint belongs
+    /// to, i.e., at least the stack or frame pointer the locations are relative to.
+    ///
+    /// Slots might be shared by multiple roots, e.g., if several derived pointers
+    /// have the same base. A collector should thus compute all relocated derived
+    /// pointers (see `GcRoot::relocate_derived`) before updating the base slots.
+    pub fn gc_roots(&self, regs: &impl RegisterProvider) -> Result<Vec<GcRoot>, GcRootError> {
+        let first_idx = self.record().location_count() - 2 * self.gc_pointer_count();
+        let mut roots = Vec::new();
+        for (pair_idx, pair) in self.gc_pointer_pairs().enumerate() {
+            let base_idx = first_idx + 2 * pair_idx;
+            let base_slot = slot_address(pair.base, base_idx, regs)?;
+            let derived_slot = slot_address(pair.derived, base_idx + 1, regs)?;
+            match (base_slot, derived_slot) {
+                (Some(base_slot), Some(derived_slot)) => roots.push(GcRoot {
+                    base_slot,
+                    derived_slot,
+                }),
+                // Constant pointers do not refer to any object.
+                (_, None) => (),
+                // A derived pointer of a constant base is an unrelocatable pointer.
+                (None, Some(_)) => return Err(GcRootError::NotInMemory(base_idx)),
+            }
+        }
+        Ok(roots)
+    }
+}
+
+impl StackMapIndex<'_> {
+    /// Get the stack slots of all GC pointers that are live in the frame that
+    /// returns to `return_address` (see `StatepointRecord::gc_roots`).
+    pub fn gc_roots(
+        &self,
+        return_address: u64,
+        regs: &impl RegisterProvider,
+    ) -> Result<Vec<GcRoot>, GcRootError> {
+        let indexed = self
+            .record_at(return_address)
+            .ok_or(GcRootError::NoRecord(return_address))?;
+        StatepointRecord::new(indexed.record)?.gc_roots(regs)
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::{StackMap, StkMapRecord, StkSizeRecord, Symbols};
+
+/// A record found via a `StackMapIndex`, together with the function and the
+/// stackmap it belongs to.
+#[derive(Debug, Clone, Copy)]
+pub struct IndexedRecord<'a> {
+    pub map: &'a StackMap,
+    pub function: &'a StkSizeRecord,
+    pub record: &'a StkMapRecord,
+}
+
+impl IndexedRecord<'_> {
+    /// The large constants of the stackmap the record belongs to, as required to
+    /// evaluate its locations.
+    pub fn constants(&self) -> &[u64] {
+        self.map.constants()
+    }
+}
+
+/// A function covered by a `StackMapIndex`.
+#[derive(Debug, Clone, Copy)]
+struct IndexedFunction<'a> {
+    /// Runtime address of the function.
+    start: u64,
+    /// Runtime address of the end of the function, if its size is known.
+    end: Option<u64>,
+    function: &'a StkSizeRecord,
+}
+
+/// Index for looking up the records of stackmaps by the runtime address of
+/// their instruction, e.g., a return address found on the stack, and the
+/// functions by the addresses they contain.
+#[derive(Debug, Clone)]
+pub struct StackMapIndex<'a> {
+    load_bias: u64,
+    records: HashMap<u64, IndexedRecord<'a>>,
+    /// Sorted by their start address.
+    functions: Vec<IndexedFunction<'a>>,
+}
+
+impl<'a> StackMapIndex<'a> {
+    /// Create an index for `maps` of a binary that is loaded at its link-time
+    /// address (e.g., a non-PIE executable).
+    pub fn new(maps: &'a [StackMap]) -> StackMapIndex<'a> {
+        StackMapIndex::with_load_bias(maps, 0)
+    }
+
+    /// Create an index for `maps` of a binary that is loaded `load_bias` bytes
+    /// after its link-time address, i.e., the base address of a PIE binary.
+    pub fn with_load_bias(maps: &'a [StackMap], load_bias: u64) -> StackMapIndex<'a> {
+        let mut records = HashMap::new();
+        let mut functions = Vec::new();
+        for map in maps {
+            for (function, function_records) in map.function_records() {
+                functions.push(IndexedFunction {
+                    start: load_bias.wrapping_add(function.function_address),
+                    end: None,
+                    function,
+                });
+                for record in function_records {
+                    let address = load_bias.wrapping_add(record.address(function));
+                    // If multiple records share an address, the first one is used.
+                    records.entry(address).or_insert(IndexedRecord {
+                        map,
+                        function,
+                        record,
+                    });
+                }
+            }
+        }
+        functions.sort_by_key(|f| f.start);
+        StackMapIndex {
+            load_bias,
+            records,
+            functions,
+        }
+    }
+
+    /// Use the sizes of the function symbols in `symbols` to determine where
+    /// functions end. Without sizes, a function is assumed to extend up to the
+    /// next function in the index, and the end of the last function is unknown.
+    pub fn with_symbols(mut self, symbols: &Symbols) -> StackMapIndex<'a> {
+        for f in self.functions.iter_mut() {
+            let symbol = symbols.get(f.function.function_address);
+            if let Some(symbol) = symbol.filter(|s| s.size != 0) {
+                f.end = Some(f.start.wrapping_add(symbol.size));
+            }
+        }
+        self
+    }
+
+    /// The offset between runtime and link-time addresses.
+    pub fn load_bias(&self) -> u64 {
+        self.load_bias
+    }
+
+    /// Get the record whose instruction is located at the runtime `address`.
+    pub fn record_at(&self, address: u64) -> Option<IndexedRecord<'a>> {
+        self.records.get(&address).copied()
+    }
+
+    /// Get the function that contains the runtime address `pc`. Returns None if
+    /// the end of the function preceding `pc` is unknown, i.e., if it is the last
+    /// function and no symbol size is known for it (see `with_symbols`), since
+    /// `pc` may then belong to code not covered by the stackmaps.
+    pub fn function_at(&self, pc: u64) -> Option<&'a StkSizeRecord> {
+        let idx = self
+            .functions
+            .partition_point(|f| f.start <= pc)
+            .checked_sub(1)?;
+        let candidate = &self.functions[idx];
+        let end = candidate
+            .end
+            .or_else(|| self.functions.get(idx + 1).map(|next| next.start))?;
+        (pc < end).then_some(candidate.function)
+    }
+
+    /// The number of indexed records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
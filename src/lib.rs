@@ -4,4 +4,78 @@ mod stackmap;
 pub use crate::stackmap::*;
 
 mod instruction;
-pub use instruction::*;
\ No newline at end of file
+pub use instruction::*;
+
+mod arch;
+pub use arch::*;
+
+mod archive;
+
+mod callconv;
+pub use callconv::*;
+
+#[cfg(feature = "dwarf")]
+mod cfi;
+#[cfg(feature = "dwarf")]
+pub use cfi::*;
+
+#[cfg(feature = "from-elf")]
+mod debuginfo;
+#[cfg(feature = "from-elf")]
+pub use debuginfo::*;
+
+mod deopt;
+pub use deopt::*;
+
+mod diff;
+pub use diff::*;
+
+mod eval;
+pub use eval::*;
+
+mod format;
+pub use format::*;
+
+mod gc;
+pub use gc::*;
+
+mod index;
+pub use index::*;
+
+#[cfg(feature = "dwarf")]
+mod source;
+#[cfg(feature = "dwarf")]
+pub use source::*;
+
+#[cfg(feature = "from-macho")]
+mod macho;
+
+mod patch;
+pub use patch::*;
+
+#[cfg(feature = "from-pe")]
+mod pe;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+
+mod options;
+pub use options::*;
+
+mod shadow;
+pub use shadow::*;
+
+mod statepoint;
+pub use statepoint::*;
+
+mod symbols;
+pub use symbols::*;
+
+mod validate;
+pub use validate::*;
+
+mod vector;
+pub use vector::*;
+
+mod walk;
+pub use walk::*;
@@ -0,0 +1,205 @@
+use std::{fs, path::Path};
+
+use goblin::mach::{
+    constants::cputype::{CPU_TYPE_ARM64, CPU_TYPE_X86_64},
+    header::MH_OBJECT,
+    relocation::{ARM64_RELOC_UNSIGNED, X86_64_RELOC_UNSIGNED},
+    segment::Section,
+    symbols::N_SECT,
+    Mach, MachO, SingleArch,
+};
+
+use crate::{Arch, ParseOptions, ParsingError, StackMap, Symbol, Symbols};
+
+impl Arch {
+    /// Get the architecture of a Mach-O file based on its `cputype` value.
+    pub fn from_macho_cputype(cputype: u32) -> Option<Arch> {
+        match cputype {
+            CPU_TYPE_X86_64 => Some(Arch::X86_64),
+            CPU_TYPE_ARM64 => Some(Arch::AArch64),
+            _ => None,
+        }
+    }
+
+    /// The architecture this crate was compiled for, if it is known.
+    pub fn host() -> Option<Arch> {
+        if cfg!(target_arch = "x86_64") {
+            Some(Arch::X86_64)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Arch::AArch64)
+        } else {
+            None
+        }
+    }
+}
+
+impl Symbols {
+    /// Collect the function symbols of `macho`. The leading underscore that is
+    /// prepended to all C symbol names on Mach-O is removed. Since Mach-O does not
+    /// record symbol sizes, the size of all symbols is zero.
+    pub fn from_macho(macho: &MachO) -> Symbols {
+        macho
+            .symbols()
+            .filter_map(|sym| sym.ok())
+            .filter(|(_, nlist)| !nlist.is_stab() && nlist.get_type() == N_SECT)
+            .map(|(name, nlist)| Symbol {
+                name: name.strip_prefix('_').unwrap_or(name).to_owned(),
+                address: nlist.n_value,
+                size: 0,
+            })
+            .collect()
+    }
+}
+
+impl StackMap {
+    /// Check whether the section `sectname` of segment `segname` is one of the
+    /// sections listed in `options`. Names can either be given as the plain
+    /// section name or as `segname,sectname`.
+    fn is_macho_stackmap_section(options: &ParseOptions, segname: &str, sectname: &str) -> bool {
+        let qualified = format!("{},{}", segname, sectname);
+        options
+            .section_names
+            .iter()
+            .any(|name| name == sectname || *name == qualified)
+    }
+
+    /// Apply the relocations of the stack map `section` of the relocatable object
+    /// `macho`. The function addresses become addresses in the address space of
+    /// the object, in which its sections are laid out one after another.
+    fn relocate_macho_stackmap_section(
+        macho: &MachO,
+        section: &Section,
+        stack_map_section: &mut [u8],
+    ) -> Result<(), ParsingError> {
+        let unsigned = match Arch::from_macho_cputype(macho.header.cputype) {
+            Some(Arch::X86_64) => X86_64_RELOC_UNSIGNED,
+            Some(Arch::AArch64) => ARM64_RELOC_UNSIGNED,
+            None => {
+                return Err(ParsingError::Malformed(format!(
+                    "Unsupported CPU type {}",
+                    macho.header.cputype
+                )))
+            }
+        };
+
+        for (_, relocs, reloc_section) in macho.relocations()? {
+            // Skip relocs for other sections then the stack map.
+            if reloc_section.segname()? != section.segname()?
+                || reloc_section.name()? != section.name()?
+            {
+                continue;
+            }
+
+            for reloc in relocs {
+                let reloc = reloc?;
+                // Only absolute 64 bit relocations are expected for function addresses.
+                if reloc.r_type() != unsigned || reloc.r_length() != 3 || reloc.is_pic() {
+                    return Err(ParsingError::Malformed(format!(
+                        "Unsupported relocation for stack map: {}",
+                        reloc.r_type()
+                    )));
+                }
+
+                let offset = usize::try_from(reloc.r_address)
+                    .map_err(|_| ParsingError::Malformed("Invalid relocation offset".to_owned()))?;
+                let slot = stack_map_section
+                    .get_mut(offset..offset.saturating_add(8))
+                    .ok_or_else(|| {
+                        ParsingError::Malformed(
+                            "Relocation exceeds the stack map section".to_owned(),
+                        )
+                    })?;
+                // Relocations against a section already hold the address of the
+                // target, those against a symbol hold the addend.
+                if !reloc.is_extern() {
+                    continue;
+                }
+                let symbols = macho.symbols.as_ref().ok_or_else(|| {
+                    ParsingError::Malformed("Relocation without symbol table".to_owned())
+                })?;
+                let (_, nlist) = symbols.get(reloc.r_symbolnum())?;
+                if nlist.get_type() != N_SECT {
+                    return Err(ParsingError::Malformed(
+                        "Relocation refers to an undefined symbol".to_owned(),
+                    ));
+                }
+                let addend = u64::from_le_bytes(slot[..].try_into().unwrap());
+                slot.copy_from_slice(&nlist.n_value.wrapping_add(addend).to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the stackmap(s) of a single-architecture Mach-O binary. For
+    /// relocatable objects, the function addresses are addresses in the address
+    /// space of the object (see `relocate_macho_stackmap_section`).
+    fn from_macho(macho: &MachO, options: &ParseOptions) -> Result<Vec<StackMap>, ParsingError> {
+        for segment in macho.segments.iter() {
+            for (section, data) in segment.sections()? {
+                let is_stackmap = StackMap::is_macho_stackmap_section(
+                    options,
+                    section.segname()?,
+                    section.name()?,
+                );
+                if !is_stackmap {
+                    continue;
+                }
+
+                // Addresses in linked Mach-O files are absolute and only slid by dyld
+                // at runtime, thus only relocatable objects need to be relocated.
+                let mut data = data.to_owned();
+                if macho.header.filetype == MH_OBJECT {
+                    StackMap::relocate_macho_stackmap_section(macho, &section, &mut data)?;
+                }
+                let mut maps = StackMap::new(&mut data)?;
+                let symbols = Symbols::from_macho(macho);
+                for map in maps.iter_mut() {
+                    map.symbolize(&symbols);
+                }
+                return Ok(maps);
+            }
+        }
+        Err(ParsingError::StackMapSectionNotFound)
+    }
+
+    /// Parse the stackmap(s) contained in the Mach-O file `bytes`. If `bytes` is a
+    /// universal (fat) binary, the slice for `options.arch` is used, or the one of
+    /// the host architecture if no architecture is configured.
+    pub fn from_macho_bytes(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let multi = match Mach::parse(bytes)? {
+            Mach::Binary(macho) => return StackMap::from_macho(&macho, options),
+            Mach::Fat(multi) => multi,
+        };
+
+        let arch = options.arch.or_else(Arch::host).ok_or_else(|| {
+            ParsingError::Malformed("No architecture selected for universal binary".to_owned())
+        })?;
+        for (idx, fat_arch) in multi.iter_arches().enumerate() {
+            if Arch::from_macho_cputype(fat_arch?.cputype) != Some(arch) {
+                continue;
+            }
+            return match multi.get(idx)? {
+                SingleArch::MachO(macho) => StackMap::from_macho(&macho, options),
+                SingleArch::Archive(_) => Err(ParsingError::Malformed(
+                    "Archives in universal binaries are not supported".to_owned(),
+                )),
+            };
+        }
+        Err(ParsingError::Malformed(format!(
+            "Universal binary does not contain a slice for {:?}",
+            arch
+        )))
+    }
+
+    /// Parse the stackmap(s) of the Mach-O binary `path` points to (see `from_macho_bytes`).
+    pub fn from_macho_path<T: AsRef<Path>>(
+        path: T,
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let bytes = fs::read(path)?;
+        StackMap::from_macho_bytes(&bytes, options)
+    }
+}
@@ -0,0 +1,81 @@
+use std::{fs::File, io::Cursor, path::Path};
+
+use goblin::{
+    container::Ctx,
+    elf::{
+        header::ET_REL,
+        reloc::RelocSection,
+        section_header::{SHT_DYNSYM, SHT_REL, SHT_RELA},
+        sym::Symtab,
+        Elf,
+    },
+    strtab::Strtab,
+};
+use memmap2::Mmap;
+
+use crate::{ParseOptions, ParsingError, StackMap};
+
+impl StackMap {
+    /// Create an `Elf` for the mapped file `bytes` that only contains the section
+    /// headers and the dynamic relocations, which are required to locate and
+    /// relocate the stackmap section. Other parts of the file are not accessed.
+    fn parse_elf_lazy(bytes: &[u8]) -> Result<Elf<'_>, ParsingError> {
+        let headers = StackMap::read_elf_headers(&mut Cursor::new(bytes))?;
+        // Relocatable objects are small, but their symbols and relocations are needed.
+        if headers.header.e_type == ET_REL {
+            return Ok(Elf::parse(bytes)?);
+        }
+        let ctx = Ctx::new(headers.header.container()?, headers.header.endianness()?);
+        let mut elf = Elf::lazy_parse(headers.header)?;
+
+        if let Some(shstrtab) = headers.section_headers.get(headers.shstrndx) {
+            elf.shdr_strtab = Strtab::parse(
+                bytes,
+                shstrtab.sh_offset as usize,
+                shstrtab.sh_size as usize,
+                0,
+            )?;
+        }
+
+        for section in headers.section_headers.iter() {
+            let offset = section.sh_offset as usize;
+            let size = section.sh_size as usize;
+            match section.sh_type {
+                SHT_DYNSYM if section.sh_entsize != 0 => {
+                    let count = size / section.sh_entsize as usize;
+                    elf.dynsyms = Symtab::parse(bytes, offset, count, ctx)?;
+                }
+                SHT_RELA if elf.shdr_strtab.get_at(section.sh_name) == Some(".rela.dyn") => {
+                    elf.dynrelas = RelocSection::parse(bytes, offset, size, true, ctx)?;
+                }
+                SHT_REL if elf.shdr_strtab.get_at(section.sh_name) == Some(".rel.dyn") => {
+                    elf.dynrels = RelocSection::parse(bytes, offset, size, false, ctx)?;
+                }
+                _ => (),
+            }
+        }
+
+        elf.section_headers = headers.section_headers;
+        Ok(elf)
+    }
+
+    /// Parse the stackmap(s) of the ELF binary `path` points to by mapping it into
+    /// memory. Only the headers, the dynamic relocations, and the stackmap section
+    /// are accessed, which makes this considerably faster than `from_path` for
+    /// huge binaries. The functions of the returned stackmaps are not symbolized
+    /// (see `StackMap::symbolize`).
+    ///
+    /// The file must not be modified while it is parsed, since the mapping would
+    /// change underneath the parser.
+    pub fn from_path_mmap<T: AsRef<Path>>(
+        path: T,
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let file = File::open(path)?;
+        // Safety: The mapping is only read while parsing and dropped afterwards,
+        // all data of the returned stackmaps is copied out of it.
+        let bytes = unsafe { Mmap::map(&file)? };
+        let elf = StackMap::parse_elf_lazy(&bytes)?;
+        StackMap::from_elf(&elf, &bytes, options)
+    }
+}
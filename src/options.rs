@@ -0,0 +1,45 @@
+use crate::Arch;
+
+/// Options that control how a stackmap is located in a binary.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Names of the sections that might contain the stackmap. The first section
+    /// that exists in the binary is used.
+    pub section_names: Vec<String>,
+    /// The architecture whose slice is used if a binary contains code for multiple
+    /// architectures (e.g., Mach-O universal binaries). If None, the host
+    /// architecture is used.
+    pub arch: Option<Arch>,
+}
+
+/// By default, the section names used by LLVM are searched.
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            section_names: vec![".llvm_stackmaps".to_owned(), "__llvm_stackmaps".to_owned()],
+            arch: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Create options that only search the sections named `section_names`.
+    pub fn new<T: Into<String>>(section_names: impl IntoIterator<Item = T>) -> ParseOptions {
+        ParseOptions {
+            section_names: section_names.into_iter().map(Into::into).collect(),
+            arch: None,
+        }
+    }
+
+    /// Additionally search the section `section_name`.
+    pub fn with_section_name<T: Into<String>>(mut self, section_name: T) -> ParseOptions {
+        self.section_names.push(section_name.into());
+        self
+    }
+
+    /// Select the architecture `arch` for binaries containing multiple architectures.
+    pub fn with_arch(mut self, arch: Arch) -> ParseOptions {
+        self.arch = Some(arch);
+        self
+    }
+}
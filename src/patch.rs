@@ -0,0 +1,327 @@
+use std::fmt;
+
+#[cfg(any(feature = "from-elf", target_os = "linux"))]
+use std::path::Path;
+
+#[cfg(feature = "from-elf")]
+use {
+    crate::{shadow::elf_code_sections, ParsingError},
+    goblin::elf::Elf,
+    std::{fs, io},
+};
+
+#[cfg(target_os = "linux")]
+use std::{fs::File, os::unix::fs::FileExt};
+
+use crate::{shadow::AARCH64_NOP, Arch, MemoryProvider, StkMapRecord, StkSizeRecord};
+
+/// Code that can be patched, e.g., the content of a binary or the memory of a
+/// running process.
+pub trait CodeWriter: MemoryProvider {
+    /// Write `bytes` to `address`. Returns false if the memory could not be
+    /// written.
+    fn write_code(&mut self, address: u64, bytes: &[u8]) -> bool;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// A patch of the given size does not fit into the shadow.
+    TooLarge { size: usize, shadow_size: usize },
+    /// The shadow at the given address does not only consist of NOPs, e.g.,
+    /// because it was already patched or clobbered.
+    NotNops(u64),
+    /// The remainder of the shadow after the patch can not be filled with NOPs
+    /// of the architecture, e.g., because it is not a multiple of 4 bytes on
+    /// AArch64.
+    UnalignedPadding(usize),
+    /// The code at the given address could not be read.
+    MemoryUnreadable(u64),
+    /// The code at the given address could not be written.
+    MemoryUnwritable(u64),
+    /// The patched code at the given address was modified after the patch was
+    /// applied, so restoring it would clobber the modification.
+    Modified(u64),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::TooLarge { size, shadow_size } => write!(
+                f,
+                "patch of {} bytes does not fit into a shadow of {} bytes",
+                size, shadow_size
+            ),
+            PatchError::NotNops(address) => {
+                write!(f, "the shadow at {:#x} does not consist of NOPs", address)
+            }
+            PatchError::UnalignedPadding(size) => {
+                write!(f, "{} bytes can not be filled with NOPs", size)
+            }
+            PatchError::MemoryUnreadable(address) => {
+                write!(f, "failed to read code at {:#x}", address)
+            }
+            PatchError::MemoryUnwritable(address) => {
+                write!(f, "failed to write code at {:#x}", address)
+            }
+            PatchError::Modified(address) => {
+                write!(f, "the patched code at {:#x} was modified", address)
+            }
+        }
+    }
+}
+
+impl Arch {
+    /// Get `len` bytes of NOP instructions, or None if `len` can not be filled
+    /// with NOPs of this architecture.
+    pub fn nops(&self, len: usize) -> Option<Vec<u8>> {
+        match self {
+            Arch::X86_64 => Some(vec![0x90; len]),
+            Arch::AArch64 => (len % 4 == 0).then(|| AARCH64_NOP.repeat(len / 4)),
+        }
+    }
+}
+
+/// The shadow of a record that can be patched, e.g., to call instrumentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PatchSite {
+    /// Address of the record's instruction, i.e., the start of the shadow.
+    pub address: u64,
+    /// The number of bytes reserved for patching (see `StackMap::verify_shadows`).
+    pub shadow_size: usize,
+}
+
+impl PatchSite {
+    /// Create a patch site for `record` of `function` whose shadow spans
+    /// `shadow_size` bytes. The site is located at the link-time address of the
+    /// record, use `with_load_bias` to patch a running process.
+    pub fn new(function: &StkSizeRecord, record: &StkMapRecord, shadow_size: usize) -> PatchSite {
+        PatchSite {
+            address: record.address(function),
+            shadow_size,
+        }
+    }
+
+    /// Move the site by `load_bias` bytes, i.e., the base address of a PIE binary.
+    pub fn with_load_bias(mut self, load_bias: u64) -> PatchSite {
+        self.address = self.address.wrapping_add(load_bias);
+        self
+    }
+
+    /// Write `bytes` to the start of the shadow and fill the rest of the shadow
+    /// with single NOP instructions. The patch is only applied if it fits into
+    /// the shadow and the shadow still consists of NOPs only.
+    ///
+    /// When patching a running process, the caller must ensure that no thread
+    /// executes the shadow while it is written.
+    pub fn patch(
+        &self,
+        arch: Arch,
+        target: &mut impl CodeWriter,
+        bytes: &[u8],
+    ) -> Result<AppliedPatch, PatchError> {
+        if bytes.len() > self.shadow_size {
+            return Err(PatchError::TooLarge {
+                size: bytes.len(),
+                shadow_size: self.shadow_size,
+            });
+        }
+        let mut original = vec![0u8; self.shadow_size];
+        if !target.read_memory(self.address, &mut original) {
+            return Err(PatchError::MemoryUnreadable(self.address));
+        }
+        if arch.nop_sled_len(&original) < self.shadow_size {
+            return Err(PatchError::NotNops(self.address));
+        }
+
+        let padding_size = self.shadow_size - bytes.len();
+        let padding = arch
+            .nops(padding_size)
+            .ok_or(PatchError::UnalignedPadding(padding_size))?;
+        let patched = [bytes, &padding].concat();
+        if !target.write_code(self.address, &patched) {
+            return Err(PatchError::MemoryUnwritable(self.address));
+        }
+        Ok(AppliedPatch {
+            address: self.address,
+            original,
+            patched,
+        })
+    }
+
+    /// Encode an x86_64 `call rel32` located at the start of the shadow that
+    /// calls `target`. Returns None if `target` is out of range.
+    pub fn x86_64_call(&self, target: u64) -> Option<[u8; 5]> {
+        self.x86_64_rel32(0xe8, target)
+    }
+
+    /// Encode an x86_64 `jmp rel32` located at the start of the shadow that jumps
+    /// to `target`. Returns None if `target` is out of range.
+    pub fn x86_64_jmp(&self, target: u64) -> Option<[u8; 5]> {
+        self.x86_64_rel32(0xe9, target)
+    }
+
+    fn x86_64_rel32(&self, opcode: u8, target: u64) -> Option<[u8; 5]> {
+        // The displacement is relative to the end of the 5 byte instruction.
+        let next = self.address.wrapping_add(5);
+        let displacement = i32::try_from(target.wrapping_sub(next) as i64).ok()?;
+        let mut instruction = [opcode, 0, 0, 0, 0];
+        instruction[1..].copy_from_slice(&displacement.to_le_bytes());
+        Some(instruction)
+    }
+}
+
+/// A patch written by `PatchSite::patch`, which can be used to restore the
+/// original code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedPatch {
+    pub address: u64,
+    /// The content of the shadow before the patch.
+    pub original: Vec<u8>,
+    /// The content of the shadow after the patch, including the NOP padding.
+    pub patched: Vec<u8>,
+}
+
+impl AppliedPatch {
+    /// Restore the original content of the shadow. Fails if the shadow does not
+    /// contain the patch anymore.
+    pub fn restore(&self, target: &mut impl CodeWriter) -> Result<(), PatchError> {
+        let mut current = vec![0u8; self.patched.len()];
+        if !target.read_memory(self.address, &mut current) {
+            return Err(PatchError::MemoryUnreadable(self.address));
+        }
+        if current != self.patched {
+            return Err(PatchError::Modified(self.address));
+        }
+        if !target.write_code(self.address, &self.original) {
+            return Err(PatchError::MemoryUnwritable(self.address));
+        }
+        Ok(())
+    }
+}
+
+/// The content of an ELF file whose code is patched at its link-time addresses,
+/// e.g., to instrument a binary before running it.
+#[cfg(feature = "from-elf")]
+#[derive(Debug, Clone)]
+pub struct ElfImage {
+    bytes: Vec<u8>,
+    /// (address, file offset, size) of the executable sections.
+    sections: Vec<(u64, usize, usize)>,
+}
+
+#[cfg(feature = "from-elf")]
+impl ElfImage {
+    /// Create an image from the content of an ELF file. Its code is located via
+    /// the executable sections (see `Code::from_elf`).
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<ElfImage, ParsingError> {
+        let elf = Elf::parse(&bytes)?;
+        let sections = elf_code_sections(&elf)?
+            .into_iter()
+            .map(|section| {
+                (
+                    section.sh_addr,
+                    section.sh_offset as usize,
+                    section.sh_size as usize,
+                )
+            })
+            .filter(|(_, offset, size)| {
+                offset
+                    .checked_add(*size)
+                    .map_or(false, |end| end <= bytes.len())
+            })
+            .collect();
+        Ok(ElfImage { bytes, sections })
+    }
+
+    /// Create an image from the ELF file `path` points to.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Result<ElfImage, ParsingError> {
+        ElfImage::from_bytes(fs::read(path)?)
+    }
+
+    /// The (patched) content of the file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Write the (patched) content to the file `path` points to.
+    pub fn write_to<T: AsRef<Path>>(&self, path: T) -> io::Result<()> {
+        fs::write(path, &self.bytes)
+    }
+
+    /// Get the file range of `len` bytes at `address`, if they are contained in
+    /// a single executable section.
+    fn file_range(&self, address: u64, len: usize) -> Option<std::ops::Range<usize>> {
+        self.sections.iter().find_map(|(start, offset, size)| {
+            let section_offset = usize::try_from(address.checked_sub(*start)?).ok()?;
+            let end = section_offset.checked_add(len)?;
+            (end <= *size).then(|| offset + section_offset..offset + end)
+        })
+    }
+}
+
+#[cfg(feature = "from-elf")]
+impl MemoryProvider for ElfImage {
+    fn read_memory(&self, address: u64, buf: &mut [u8]) -> bool {
+        match self.file_range(address, buf.len()) {
+            Some(range) => {
+                buf.copy_from_slice(&self.bytes[range]);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(feature = "from-elf")]
+impl CodeWriter for ElfImage {
+    fn write_code(&mut self, address: u64, bytes: &[u8]) -> bool {
+        match self.file_range(address, bytes.len()) {
+            Some(range) => {
+                self.bytes[range].copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The memory of a running process, accessed via `/proc/<pid>/mem`. This allows
+/// writing to read-only code mappings, but requires permission to trace the
+/// process (e.g., being attached via ptrace) unless it is the current process.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct ProcessMemory {
+    file: File,
+}
+
+#[cfg(target_os = "linux")]
+impl ProcessMemory {
+    /// Open the memory of the process `pid`.
+    pub fn open(pid: u32) -> std::io::Result<ProcessMemory> {
+        ProcessMemory::from_path(format!("/proc/{}/mem", pid))
+    }
+
+    /// Open the memory of the current process.
+    pub fn current() -> std::io::Result<ProcessMemory> {
+        ProcessMemory::from_path("/proc/self/mem")
+    }
+
+    fn from_path(path: impl AsRef<Path>) -> std::io::Result<ProcessMemory> {
+        let file = File::options().read(true).write(true).open(path)?;
+        Ok(ProcessMemory { file })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl MemoryProvider for ProcessMemory {
+    fn read_memory(&self, address: u64, buf: &mut [u8]) -> bool {
+        self.file.read_exact_at(buf, address).is_ok()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl CodeWriter for ProcessMemory {
+    fn write_code(&mut self, address: u64, bytes: &[u8]) -> bool {
+        self.file.write_all_at(bytes, address).is_ok()
+    }
+}
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use goblin::{
+    pe::{
+        header::{COFF_MACHINE_ARM64, COFF_MACHINE_X86_64},
+        relocation::{
+            IMAGE_REL_AMD64_ADDR64, IMAGE_REL_ARM64_ADDR64, IMAGE_REL_BASED_ABSOLUTE,
+            IMAGE_REL_BASED_DIR64,
+        },
+        section_table::SectionTable,
+        symbol::{SymbolTable, IMAGE_SYM_DTYPE_FUNCTION},
+        Coff, PE,
+    },
+    strtab::Strtab,
+};
+
+use crate::{Arch, ParseOptions, ParsingError, StackMap, Symbol, Symbols};
+
+impl Arch {
+    /// Get the architecture of a COFF/PE file based on its `Machine` header field.
+    pub fn from_coff_machine(machine: u16) -> Option<Arch> {
+        match machine {
+            COFF_MACHINE_X86_64 => Some(Arch::X86_64),
+            COFF_MACHINE_ARM64 => Some(Arch::AArch64),
+            _ => None,
+        }
+    }
+}
+
+impl Symbols {
+    /// Collect the function symbols of a COFF symbol table. The addresses of the
+    /// symbols are relative to the image base (RVAs). Since COFF does not record
+    /// symbol sizes, the size of all symbols is zero.
+    pub fn from_coff(
+        symbols: &SymbolTable,
+        strings: Option<&Strtab>,
+        sections: &[SectionTable],
+    ) -> Symbols {
+        symbols
+            .iter()
+            .filter(|(_, _, sym)| sym.derived_type() == IMAGE_SYM_DTYPE_FUNCTION)
+            .filter_map(|(_, name, sym)| {
+                // Section numbers are one-based, zero and negative values are special.
+                let section_idx = usize::try_from(sym.section_number).ok()?.checked_sub(1)?;
+                let section = sections.get(section_idx)?;
+                let name = match name {
+                    Some(name) => name,
+                    None => sym.name(strings?).ok()?,
+                };
+                Some(Symbol {
+                    name: name.to_owned(),
+                    address: section.virtual_address as u64 + sym.value as u64,
+                    size: 0,
+                })
+            })
+            .collect()
+    }
+}
+
+impl StackMap {
+    /// Check whether the section `name` is one of the sections listed in `options`.
+    /// Since section names of images are truncated to eight bytes by some linkers,
+    /// a truncated name also matches.
+    fn is_pe_stackmap_section(options: &ParseOptions, name: &str) -> bool {
+        options.section_names.iter().any(|section_name| {
+            section_name == name
+                || (name.len() == 8 && section_name.len() > 8 && section_name.starts_with(name))
+        })
+    }
+
+    /// Find the stackmap section of `sections` and get its content.
+    fn read_pe_section<'a>(
+        sections: &'a [SectionTable],
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<(&'a SectionTable, Vec<u8>), ParsingError> {
+        for section in sections.iter() {
+            if !StackMap::is_pe_stackmap_section(options, section.name()?) {
+                continue;
+            }
+            let mut data = section
+                .data(bytes)?
+                .ok_or_else(|| {
+                    ParsingError::Malformed("Stack map section exceeds the file".to_owned())
+                })?
+                .into_owned();
+            // The raw data of images is padded to the file alignment.
+            if section.virtual_size != 0 {
+                data.truncate(section.virtual_size as usize);
+            }
+            return Ok((section, data));
+        }
+        Err(ParsingError::StackMapSectionNotFound)
+    }
+
+    /// Apply the `IMAGE_REL_BASED_DIR64` base relocations of `pe` that target the
+    /// stack map `section`. Like for position independent ELF binaries, the function
+    /// addresses are relocated as if the image is loaded at address zero, i.e., they
+    /// become RVAs. Images without base relocations are handled by `from_pe_image`.
+    fn relocate_pe_stackmap_section(
+        pe: &PE,
+        section: &SectionTable,
+        stack_map_section: &mut [u8],
+    ) -> Result<(), ParsingError> {
+        let relocation_data = match pe.relocation_data.as_ref() {
+            Some(relocation_data) => relocation_data,
+            None => return Ok(()),
+        };
+
+        let start = section.virtual_address as u64;
+        let section_range = start..start + stack_map_section.len() as u64;
+        for block in relocation_data.blocks() {
+            let block = block?;
+            for word in block.words() {
+                let word = word?;
+                let rva = block.rva as u64 + word.offset() as u64;
+
+                // Skip relocs for other sections then the stack map.
+                if !section_range.contains(&rva) {
+                    continue;
+                }
+
+                let offset = (rva - section_range.start) as usize;
+                match word.reloc_type() as u16 {
+                    IMAGE_REL_BASED_ABSOLUTE => (),
+                    IMAGE_REL_BASED_DIR64 => {
+                        let slot =
+                            stack_map_section
+                                .get_mut(offset..offset + 8)
+                                .ok_or_else(|| {
+                                    ParsingError::Malformed(
+                                        "Relocation exceeds the stack map section".to_owned(),
+                                    )
+                                })?;
+                        let value = u64::from_le_bytes(slot[..].try_into().unwrap());
+                        slot.copy_from_slice(&value.wrapping_sub(pe.image_base).to_le_bytes());
+                    }
+                    reloc_type => {
+                        return Err(ParsingError::Malformed(format!(
+                            "Unsupported base relocation for stack map: {}",
+                            reloc_type
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the stackmap(s) of a PE image. Function addresses are RVAs, and the
+    /// functions are symbolized if the image contains a COFF symbol table.
+    fn from_pe_image(bytes: &[u8], options: &ParseOptions) -> Result<Vec<StackMap>, ParsingError> {
+        let pe = PE::parse(bytes)?;
+        let (section, mut data) = StackMap::read_pe_section(&pe.sections, bytes, options)?;
+        StackMap::relocate_pe_stackmap_section(&pe, section, &mut data)?;
+
+        let mut maps = StackMap::new(&mut data)?;
+        // Images without base relocations (e.g., linked with `/FIXED`) can only be
+        // loaded at their preferred base, so the function addresses are VAs.
+        if pe.relocation_data.is_none() {
+            for map in maps.iter_mut() {
+                for function in map.stk_size_records.iter_mut() {
+                    function.function_address =
+                        function.function_address.wrapping_sub(pe.image_base);
+                }
+            }
+        }
+        let coff_header = &pe.header.coff_header;
+        if let Some(symbols) = coff_header.symbols(bytes)? {
+            let strings = coff_header.strings(bytes)?;
+            let symbols = Symbols::from_coff(&symbols, strings.as_ref(), &pe.sections);
+            for map in maps.iter_mut() {
+                map.symbolize(&symbols);
+            }
+        }
+        Ok(maps)
+    }
+
+    /// Apply the `IMAGE_REL_*_ADDR64` relocations of the stack map `section` of the
+    /// COFF object `coff` and return the names of the relocated symbols indexed by
+    /// the offset of the relocation. Like for relocatable ELF objects, the function
+    /// addresses become offsets into the sections containing the functions.
+    fn relocate_coff_stackmap_section(
+        coff: &Coff,
+        bytes: &[u8],
+        section: &SectionTable,
+        stack_map_section: &mut [u8],
+    ) -> Result<HashMap<usize, String>, ParsingError> {
+        let addr64 = match Arch::from_coff_machine(coff.header.machine) {
+            Some(Arch::X86_64) => IMAGE_REL_AMD64_ADDR64,
+            Some(Arch::AArch64) => IMAGE_REL_ARM64_ADDR64,
+            None => {
+                return Err(ParsingError::Malformed(format!(
+                    "Unsupported machine type {}",
+                    coff.header.machine
+                )))
+            }
+        };
+
+        let mut names = HashMap::new();
+        for reloc in section.relocations(bytes)? {
+            if reloc.typ != addr64 {
+                return Err(ParsingError::Malformed(format!(
+                    "Unsupported relocation for stack map: {}",
+                    reloc.typ
+                )));
+            }
+
+            let offset = reloc.virtual_address.wrapping_sub(section.virtual_address) as usize;
+            let slot = stack_map_section
+                .get_mut(offset..offset.saturating_add(8))
+                .ok_or_else(|| {
+                    ParsingError::Malformed("Relocation exceeds the stack map section".to_owned())
+                })?;
+            let (name, sym) = coff
+                .symbols
+                .as_ref()
+                .and_then(|symbols| symbols.get(reloc.symbol_table_index as usize))
+                .ok_or_else(|| {
+                    ParsingError::Malformed("Failed to get symbol for relocation".to_owned())
+                })?;
+            // The addend is stored at the relocated location.
+            let addend = u64::from_le_bytes(slot[..].try_into().unwrap());
+            slot.copy_from_slice(&(sym.value as u64).wrapping_add(addend).to_le_bytes());
+
+            if sym.derived_type() != IMAGE_SYM_DTYPE_FUNCTION {
+                continue;
+            }
+            let name = match name {
+                Some(name) => Some(name),
+                None => coff.strings.as_ref().and_then(|s| sym.name(s).ok()),
+            };
+            if let Some(name) = name.filter(|name| !name.is_empty()) {
+                names.insert(offset, name.to_owned());
+            }
+        }
+        Ok(names)
+    }
+
+    /// Parse the stackmap(s) of a COFF object. Since the functions are not yet
+    /// placed by the linker, their addresses are offsets into the section
+    /// containing them, and their names are taken from the symbols the relocations
+    /// of the stackmap section refer to.
+    fn from_coff_object(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let coff = Coff::parse(bytes)?;
+        let (section, mut data) = StackMap::read_pe_section(&coff.sections, bytes, options)?;
+        let names = StackMap::relocate_coff_stackmap_section(&coff, bytes, section, &mut data)?;
+        StackMap::from_relocated_object_section(&data, &names)
+    }
+
+    /// Parse the stackmap(s) contained in the PE image or COFF object `bytes`.
+    /// For images, the function addresses are relative to the image base (RVAs),
+    /// for objects, they are offsets into the sections containing the functions.
+    pub fn from_pe_bytes(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        // Images start with the DOS header, objects directly with the COFF header.
+        if bytes.starts_with(b"MZ") {
+            StackMap::from_pe_image(bytes, options)
+        } else {
+            StackMap::from_coff_object(bytes, options)
+        }
+    }
+
+    /// Parse the stackmap(s) of the PE image or COFF object `path` points to
+    /// (see `from_pe_bytes`).
+    pub fn from_pe_path<T: AsRef<Path>>(
+        path: T,
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let bytes = fs::read(path)?;
+        StackMap::from_pe_bytes(&bytes, options)
+    }
+}
@@ -0,0 +1,316 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "from-elf")]
+use {
+    crate::ParsingError,
+    goblin::elf::{header, section_header, Elf, SectionHeader},
+    std::{fs, path::Path},
+};
+
+use crate::{Arch, StackMap, StkMapRecord};
+
+/// The encoding of `nop` on AArch64 in little endian byte order.
+pub(crate) const AARCH64_NOP: [u8; 4] = [0x1f, 0x20, 0x03, 0xd5];
+
+impl Arch {
+    /// Get the length of the NOP instruction at the start of `code`, or None if
+    /// `code` does not start with a NOP.
+    pub fn nop_len(&self, code: &[u8]) -> Option<usize> {
+        match self {
+            Arch::X86_64 => x86_64_nop_len(code),
+            Arch::AArch64 => code.starts_with(&AARCH64_NOP).then(|| AARCH64_NOP.len()),
+        }
+    }
+
+    /// Get the number of bytes of the NOP instructions at the start of `code`.
+    pub fn nop_sled_len(&self, code: &[u8]) -> usize {
+        let mut len = 0;
+        while let Some(nop_len) = self.nop_len(&code[len..]) {
+            len += nop_len;
+        }
+        len
+    }
+}
+
+/// Decode the `nop` variants emitted by assemblers for padding: `nop` (0x90), the
+/// multi-byte `nop r/m` (0x0f 0x1f), both optionally preceded by operand size
+/// and cs segment prefixes.
+fn x86_64_nop_len(code: &[u8]) -> Option<usize> {
+    let prefixes = code
+        .iter()
+        .take_while(|b| **b == 0x66 || **b == 0x2e)
+        .count();
+    let opcode = code.get(prefixes..)?;
+    match *opcode.first()? {
+        0x90 => Some(prefixes + 1),
+        0x0f if opcode.get(1) == Some(&0x1f) => {
+            let modrm = *opcode.get(2)?;
+            let (mode, rm) = (modrm >> 6, modrm & 0x7);
+            let mut len = prefixes + 3;
+            if mode != 3 && rm == 4 {
+                let sib = *opcode.get(3)?;
+                len += 1;
+                if mode == 0 && sib & 0x7 == 5 {
+                    len += 4;
+                }
+            }
+            len += match (mode, rm) {
+                (0, 5) => 4,
+                (1, _) => 1,
+                (2, _) => 4,
+                _ => 0,
+            };
+            (len <= code.len()).then(|| len)
+        }
+        _ => None,
+    }
+}
+
+/// The executable code of a binary at its link-time addresses, used to inspect
+/// the instructions at the addresses of records.
+#[derive(Debug, Clone)]
+pub struct Code {
+    arch: Arch,
+    /// (address, content) pairs of the executable sections.
+    sections: Vec<(u64, Vec<u8>)>,
+}
+
+/// Get the non-empty executable sections of `elf`. Since all sections of
+/// relocatable objects are located at address zero, their code can only be
+/// located by address if there is a single executable section, so objects with
+/// more sections (e.g., built with `-ffunction-sections`) are rejected.
+#[cfg(feature = "from-elf")]
+pub(crate) fn elf_code_sections<'e>(elf: &'e Elf) -> Result<Vec<&'e SectionHeader>, ParsingError> {
+    let sections = elf
+        .section_headers
+        .iter()
+        .filter(|section| {
+            section.sh_type == section_header::SHT_PROGBITS
+                && section.sh_flags & section_header::SHF_EXECINSTR as u64 != 0
+                && section.sh_size != 0
+        })
+        .collect::<Vec<_>>();
+    if elf.header.e_type == header::ET_REL && sections.len() > 1 {
+        return Err(ParsingError::Malformed(format!(
+            "Relocatable object has {} executable sections, only one is supported",
+            sections.len()
+        )));
+    }
+    Ok(sections)
+}
+
+impl Code {
+    /// Create an empty code image for `arch`.
+    pub fn new(arch: Arch) -> Code {
+        Code {
+            arch,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Add the code `bytes` located at `address`.
+    pub fn with_section(mut self, address: u64, bytes: Vec<u8>) -> Code {
+        self.sections.push((address, bytes));
+        self
+    }
+
+    /// Create a code image from the executable sections of `elf` (see
+    /// `elf_code_sections`). `bytes` must be the file `elf` was parsed from.
+    #[cfg(feature = "from-elf")]
+    pub fn from_elf(elf: &Elf, bytes: &[u8]) -> Result<Code, ParsingError> {
+        let arch = Arch::from_elf_machine(elf.header.e_machine).ok_or_else(|| {
+            ParsingError::Malformed(format!("Unsupported machine type {}", elf.header.e_machine))
+        })?;
+        let mut code = Code::new(arch);
+        for section in elf_code_sections(elf)? {
+            let data = section
+                .file_range()
+                .and_then(|range| bytes.get(range))
+                .ok_or_else(|| {
+                    ParsingError::Malformed("Code section is out of bounds".to_owned())
+                })?;
+            code = code.with_section(section.sh_addr, data.to_vec());
+        }
+        Ok(code)
+    }
+
+    /// Create a code image for the binary `path` points to (see `from_elf`).
+    #[cfg(feature = "from-elf")]
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Result<Code, ParsingError> {
+        let bytes = fs::read(path)?;
+        let elf = Elf::parse(&bytes)?;
+        Code::from_elf(&elf, &bytes)
+    }
+
+    pub fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    /// Get the code from `address` up to the end of the section containing it.
+    pub fn code_at(&self, address: u64) -> Option<&[u8]> {
+        self.sections.iter().find_map(|(start, bytes)| {
+            let offset = usize::try_from(address.checked_sub(*start)?).ok()?;
+            (offset < bytes.len()).then(|| &bytes[offset..])
+        })
+    }
+
+    /// Get the shadow of the record located at `address`, or None if the address
+    /// is not contained in the code.
+    pub fn shadow_at(&self, address: u64) -> Option<Shadow<'_>> {
+        let code = self.code_at(address)?;
+        Some(Shadow {
+            address,
+            code,
+            nop_len: self.arch.nop_sled_len(code),
+        })
+    }
+}
+
+/// The code following the instruction of a record. Patch points and stackmaps
+/// reserve a number of bytes (their shadow) there, which are filled with NOPs and
+/// can be patched at runtime.
+#[derive(Debug, Clone, Copy)]
+pub struct Shadow<'c> {
+    /// Link-time address of the record's instruction.
+    pub address: u64,
+    /// The code starting at `address` up to the end of its section.
+    pub code: &'c [u8],
+    /// The number of bytes of the NOPs starting at `address`.
+    pub nop_len: usize,
+}
+
+impl<'c> Shadow<'c> {
+    /// The NOPs starting at `address`.
+    pub fn nops(&self) -> &'c [u8] {
+        &self.code[..self.nop_len]
+    }
+}
+
+/// A problem with the shadow of a record found by `StackMap::verify_shadows`.
+/// Indices refer to `StackMap::stk_map_records`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ShadowIssue {
+    /// The address of the record is not contained in the code.
+    NotInCode { record_idx: usize, address: u64 },
+    /// There are fewer NOP bytes at the address of the record than expected.
+    ShadowTooShort {
+        record_idx: usize,
+        address: u64,
+        expected: usize,
+        nop_len: usize,
+    },
+    /// Another record is located within the expected shadow of the record.
+    ShadowOverlap {
+        record_idx: usize,
+        other_record_idx: usize,
+        other_address: u64,
+    },
+}
+
+impl fmt::Display for ShadowIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShadowIssue::NotInCode {
+                record_idx,
+                address,
+            } => write!(
+                f,
+                "record {}: address {:#x} is not contained in the code",
+                record_idx, address
+            ),
+            ShadowIssue::ShadowTooShort {
+                record_idx,
+                address,
+                expected,
+                nop_len,
+            } => write!(
+                f,
+                "record {}: expected {} bytes of NOPs at {:#x}, found {}",
+                record_idx, expected, address, nop_len
+            ),
+            ShadowIssue::ShadowOverlap {
+                record_idx,
+                other_record_idx,
+                other_address,
+            } => write!(
+                f,
+                "record {}: shadow overlaps with record {} at {:#x}",
+                record_idx, other_record_idx, other_address
+            ),
+        }
+    }
+}
+
+impl StackMap {
+    /// Check that the shadow of each record is present in `code`, i.e., that at
+    /// least `shadow_size(record)` bytes of NOPs are located at its address and no
+    /// other record is located within them. The shadow size is not part of the
+    /// stackmap and must be provided by the caller, e.g., as passed to
+    /// `llvm.experimental.stackmap` or `llvm.experimental.patchpoint`. Note that
+    /// LLVM only fills the whole shadow with NOPs for patch points without a call
+    /// target. The shadow of stackmaps may contain the instructions that follow,
+    /// and patch points with a target start with the call sequence, so the
+    /// expected size should be 0 for them.
+    ///
+    /// Function addresses must be link-time addresses, so stackmaps of position
+    /// independent binaries must not be rebased.
+    pub fn verify_shadows(
+        &self,
+        code: &Code,
+        shadow_size: impl Fn(&StkMapRecord) -> usize,
+    ) -> Vec<ShadowIssue> {
+        let records: Vec<(u64, &StkMapRecord)> = self
+            .function_records()
+            .flat_map(|(function, records)| records.iter().map(move |r| (r.address(function), r)))
+            .collect();
+        let mut by_address: Vec<(u64, usize)> = records
+            .iter()
+            .enumerate()
+            .map(|(idx, (address, _))| (*address, idx))
+            .collect();
+        by_address.sort_unstable();
+
+        let mut issues = Vec::new();
+        for (record_idx, (address, record)) in records.iter().enumerate() {
+            let expected = shadow_size(record);
+            match code.shadow_at(*address) {
+                None => issues.push(ShadowIssue::NotInCode {
+                    record_idx,
+                    address: *address,
+                }),
+                Some(shadow) if shadow.nop_len < expected => {
+                    issues.push(ShadowIssue::ShadowTooShort {
+                        record_idx,
+                        address: *address,
+                        expected,
+                        nop_len: shadow.nop_len,
+                    })
+                }
+                Some(_) => (),
+            }
+
+            if expected == 0 {
+                continue;
+            }
+            let end = address.saturating_add(expected as u64);
+            let first = by_address.partition_point(|(a, _)| a < address);
+            for (other_address, other_record_idx) in &by_address[first..] {
+                if *other_address >= end {
+                    break;
+                }
+                if *other_record_idx != record_idx {
+                    issues.push(ShadowIssue::ShadowOverlap {
+                        record_idx,
+                        other_record_idx: *other_record_idx,
+                        other_address: *other_address,
+                    });
+                }
+            }
+        }
+        issues
+    }
+}
@@ -0,0 +1,152 @@
+use std::{fs, path::Path, rc::Rc};
+
+use addr2line::Context;
+use gimli::{EndianRcSlice, RunTimeEndian};
+use goblin::elf::Elf;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{DebugFileLocator, ParsingError, StackMap, StkMapRecord, StkSizeRecord};
+
+type Reader = EndianRcSlice<RunTimeEndian>;
+
+impl From<gimli::Error> for ParsingError {
+    fn from(err: gimli::Error) -> Self {
+        ParsingError::Malformed(format!("Error while parsing DWARF: {}", err))
+    }
+}
+
+/// One frame of the inlining chain of a source location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SourceFrame {
+    /// The name of the (possibly inlined) function.
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+/// The source location of an address. If code was inlined, `frames` contains the
+/// innermost inlined function first, followed by its callers up to the function
+/// the code was inlined into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SourceLocation {
+    pub frames: Vec<SourceFrame>,
+}
+
+impl SourceLocation {
+    /// The innermost frame, i.e., the actual source location of the address.
+    pub fn innermost(&self) -> Option<&SourceFrame> {
+        self.frames.first()
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        self.innermost().and_then(|f| f.file.as_deref())
+    }
+
+    pub fn line(&self) -> Option<u32> {
+        self.innermost().and_then(|f| f.line)
+    }
+
+    pub fn column(&self) -> Option<u32> {
+        self.innermost().and_then(|f| f.column)
+    }
+}
+
+/// Resolves addresses to source locations using the DWARF debug info
+/// (`.debug_line`, `.debug_info`, ...) of a binary.
+pub struct SourceResolver {
+    ctx: Context<Reader>,
+}
+
+impl SourceResolver {
+    /// Create a resolver for the debug info contained in `elf`. `bytes` must be
+    /// the content of the file `elf` was parsed from.
+    pub fn from_elf(elf: &Elf, bytes: &[u8]) -> Result<SourceResolver, ParsingError> {
+        let endian = if elf.little_endian {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+
+        // Debug sections are commonly compressed (e.g., `-gz`).
+        let dwarf = gimli::Dwarf::load(|id| -> Result<Reader, ParsingError> {
+            let data: Rc<[u8]> = match StackMap::read_section(elf, bytes, id.name())? {
+                Some(data) => Rc::from(&*data),
+                None => Rc::from(&[][..]),
+            };
+            Ok(EndianRcSlice::new(data, endian))
+        })?;
+
+        Ok(SourceResolver {
+            ctx: Context::from_dwarf(dwarf)?,
+        })
+    }
+
+    /// Create a resolver for the debug info of the binary `path` points to.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Result<SourceResolver, ParsingError> {
+        let bytes = fs::read(path)?;
+        let elf = Elf::parse(&bytes)?;
+        SourceResolver::from_elf(&elf, &bytes)
+    }
+
+    /// Create a resolver for the debug info of the binary `path` points to. If the
+    /// binary does not contain debug info, the debug file found by `locator` is used.
+    pub fn from_path_with<T: AsRef<Path>>(
+        path: T,
+        locator: &DebugFileLocator,
+    ) -> Result<SourceResolver, ParsingError> {
+        let bytes = fs::read(path.as_ref())?;
+        let elf = Elf::parse(&bytes)?;
+        if StackMap::get_section_byte_range(&elf, ".debug_info").is_some() {
+            return SourceResolver::from_elf(&elf, &bytes);
+        }
+        match locator.find(path.as_ref(), &elf, &bytes) {
+            Some(debug_path) => SourceResolver::from_path(debug_path),
+            None => SourceResolver::from_elf(&elf, &bytes),
+        }
+    }
+
+    /// Get the source location of `address`. Returns None if there is no debug
+    /// info for the address.
+    pub fn locate(&self, address: u64) -> Result<Option<SourceLocation>, ParsingError> {
+        let mut frames = Vec::new();
+        let mut iter = self.ctx.find_frames(address).skip_all_loads()?;
+        while let Some(frame) = iter.next()? {
+            let function = match frame.function {
+                Some(name) => Some(name.raw_name()?.into_owned()),
+                None => None,
+            };
+            #[cfg(feature = "demangle")]
+            let function = function.map(|name| crate::demangle(&name));
+
+            let location = frame.location;
+            frames.push(SourceFrame {
+                function,
+                file: location.as_ref().and_then(|l| l.file.map(str::to_owned)),
+                line: location.as_ref().and_then(|l| l.line),
+                column: location.as_ref().and_then(|l| l.column),
+            });
+        }
+
+        if frames.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(SourceLocation { frames }))
+    }
+}
+
+impl StkMapRecord {
+    /// Get the source location of this record. `function` must be the function
+    /// this record belongs to.
+    pub fn source_location(
+        &self,
+        function: &StkSizeRecord,
+        resolver: &SourceResolver,
+    ) -> Result<Option<SourceLocation>, ParsingError> {
+        resolver.locate(self.address(function))
+    }
+}
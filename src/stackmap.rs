@@ -1,6 +1,7 @@
 use std::{
     convert::{TryFrom, TryInto},
-    io::{self},
+    fmt,
+    io::{self, Write},
     mem::size_of,
     path::Path,
 };
@@ -9,8 +10,22 @@ use bytes::{Buf, Bytes};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::Symbols;
+
 #[cfg(feature = "from-elf")]
-use {goblin::elf, goblin::elf::Elf, std::fs, std::ops::Range};
+use {
+    crate::{DebugFileLocator, ParseOptions},
+    goblin::elf,
+    goblin::elf::Elf,
+    goblin::strtab::Strtab,
+    std::{
+        borrow::Cow,
+        collections::HashMap,
+        fs,
+        io::{Read, Seek, SeekFrom},
+        ops::Range,
+    },
+};
 
 type Constant = u64;
 
@@ -69,16 +84,26 @@ impl From<io::Error> for ParsingError {
     }
 }
 
-#[cfg(feature = "from-elf")]
+#[cfg(any(feature = "from-elf", feature = "from-macho", feature = "from-pe"))]
 impl From<goblin::error::Error> for ParsingError {
     fn from(err: goblin::error::Error) -> Self {
         match err {
             goblin::error::Error::IO(io_err) => ParsingError::IoError(io_err),
-            err @ _ => ParsingError::Malformed(format!("Error while parsing ELF: {:?}", err)),
+            err @ _ => ParsingError::Malformed(format!("Error while parsing binary: {:?}", err)),
         }
     }
 }
 
+/// The ELF header and section headers of a binary.
+#[cfg(feature = "from-elf")]
+pub(crate) struct ElfHeaders {
+    #[cfg_attr(not(feature = "mmap"), allow(dead_code))]
+    pub header: elf::Header,
+    pub section_headers: Vec<elf::SectionHeader>,
+    /// Index of the section header string table.
+    pub shstrndx: usize,
+}
+
 /// Drain bytes from a Vector and resturn an owned Self.
 trait DrainFromBytes {
     /// This will drain size_of::<Self>() from `bytes` and return Self or an error
@@ -114,7 +139,7 @@ impl DrainFromBytes for i32 {}
 impl DrainFromBytes for i64 {}
 
 #[repr(C)]
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Header {
     /// The LLVM Stackmap version of the following data.
@@ -123,6 +148,17 @@ struct Header {
     reserved_1: u16,
 }
 
+/// By default, the header describes the only version supported by this crate.
+impl Default for Header {
+    fn default() -> Self {
+        Header {
+            version: 3,
+            reserved_0: 0,
+            reserved_1: 0,
+        }
+    }
+}
+
 impl DrainFromBytes for Header {
     fn drain_from_bytes(bytes: &mut Bytes) -> Result<Self, ParsingError>
     where
@@ -230,14 +266,40 @@ impl DrainFromBytes for Location {
 /// during execution of the PatchPoint. Whether a value must be manually saved
 /// depends on the calling convention used for a given PatchPoint.
 #[repr(C)]
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LiveOut {
     /// The register that must stay live.
-    dwarf_regnum: u16,
+    pub(crate) dwarf_regnum: u16,
     reserved_0: u8,
-    size: u8,
+    pub(crate) size: u8,
 }
+impl LiveOut {
+    /// Create a live out for the register `dwarf_regnum` with a size of `size` bytes.
+    pub fn new(dwarf_regnum: u16, size: u8) -> LiveOut {
+        LiveOut {
+            dwarf_regnum,
+            reserved_0: 0,
+            size,
+        }
+    }
+
+    /// The DWARF register number of the register that must stay live.
+    pub fn dwarf_regnum(&self) -> u16 {
+        self.dwarf_regnum
+    }
+
+    /// The reserved field, which is always zero for well-formed stackmaps.
+    pub fn reserved(&self) -> u8 {
+        self.reserved_0
+    }
+
+    /// The size of the live value in bytes.
+    pub fn size(&self) -> u8 {
+        self.size
+    }
+}
+
 impl DrainFromBytes for LiveOut {
     fn drain_from_bytes(bytes: &mut Bytes) -> Result<Self, ParsingError>
     where
@@ -270,13 +332,15 @@ pub struct StkMapRecord {
     /// Offset from start of the function this record belongs to.
     pub instruction_offset: u32,
     pub reserved_0: u16,
-    /// The number of locations this record contains.
+    /// The number of locations this record contains as stored in the stackmap.
+    /// This might differ from `location_count()` if the record was modified.
     pub num_locations: u16,
     /// Location of the values this patch point was instructed to record.
     pub locations: Vec<Location>,
     // conditional_padding_0: u32,
     // conditional_padding_1: u16,
-    /// The number of live outs in this record.
+    /// The number of live outs in this record as stored in the stackmap.
+    /// This might differ from `live_out_count()` if the record was modified.
     pub num_live_outs: u16,
     /// The live out values.
     pub live_outs: Vec<LiveOut>,
@@ -289,11 +353,14 @@ pub struct StkMapRecord {
 pub struct StackMap {
     /// The stackmap header.
     header: Header,
-    /// Number of entries in stk_size_records.
+    /// Number of entries in stk_size_records as stored in the stackmap. This might differ
+    /// from `function_count()` if the stackmap is malformed or was modified.
     pub num_functions: u32,
-    /// Number of entries in large_constants.
+    /// Number of entries in large_constants as stored in the stackmap. This might differ
+    /// from `constant_count()` if the stackmap is malformed or was modified.
     pub num_constants: u32,
-    /// Number of entries in stk_map_records.
+    /// Number of entries in stk_map_records as stored in the stackmap. This might differ
+    /// from `record_count()` if the stackmap is malformed or was modified.
     pub num_records: u32,
     /// One record for each function that contains patch points.
     pub stk_size_records: Vec<StkSizeRecord>,
@@ -301,10 +368,104 @@ pub struct StackMap {
     pub large_constants: Vec<Constant>,
     /// One record for each patch point.
     pub stk_map_records: Vec<StkMapRecord>,
+    /// The symbols at the addresses of the functions. They are not part of the
+    /// stackmap and only available if it was symbolized (see `symbolize`).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Symbols::is_empty")
+    )]
+    pub(crate) symbols: Symbols,
 }
 
 impl StkMapRecord {
-    fn new(data: &mut Bytes, stream_offset: &mut usize) -> Result<StkMapRecord, ParsingError> {
+    /// Create a record with the given `locations` and `live_outs`. The `num_locations`
+    /// and `num_live_outs` fields are derived from the passed vectors.
+    pub fn new(
+        patch_point_id: u64,
+        instruction_offset: u32,
+        locations: Vec<Location>,
+        live_outs: Vec<LiveOut>,
+    ) -> Result<StkMapRecord, String> {
+        let num_locations = u16::try_from(locations.len())
+            .map_err(|_| format!("Too many locations: {}", locations.len()))?;
+        let num_live_outs = u16::try_from(live_outs.len())
+            .map_err(|_| format!("Too many live outs: {}", live_outs.len()))?;
+
+        Ok(StkMapRecord {
+            patch_point_id,
+            instruction_offset,
+            reserved_0: 0,
+            num_locations,
+            locations,
+            num_live_outs,
+            live_outs,
+        })
+    }
+
+    /// The absolute address of this record. `function` must be the function
+    /// this record belongs to.
+    pub fn address(&self, function: &StkSizeRecord) -> u64 {
+        function
+            .function_address
+            .wrapping_add(self.instruction_offset as u64)
+    }
+
+    /// The locations of this record.
+    pub fn locations(&self) -> &[Location] {
+        &self.locations
+    }
+
+    /// The live outs of this record.
+    pub fn live_outs(&self) -> &[LiveOut] {
+        &self.live_outs
+    }
+
+    /// The number of locations, derived from the locations actually present.
+    /// Use `num_locations` to get the value stored in the stackmap.
+    pub fn location_count(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// The number of live outs, derived from the live outs actually present.
+    /// Use `num_live_outs` to get the value stored in the stackmap.
+    pub fn live_out_count(&self) -> usize {
+        self.live_outs.len()
+    }
+
+    /// Append `location` to the record while keeping `num_locations` in sync.
+    pub fn push_location(&mut self, location: Location) -> Result<(), String> {
+        let num_locations =
+            u16::try_from(self.locations.len() + 1).map_err(|_| "Too many locations".to_owned())?;
+        self.locations.push(location);
+        self.num_locations = num_locations;
+        Ok(())
+    }
+
+    /// Append `live_out` to the record while keeping `num_live_outs` in sync.
+    pub fn push_live_out(&mut self, live_out: LiveOut) -> Result<(), String> {
+        let num_live_outs =
+            u16::try_from(self.live_outs.len() + 1).map_err(|_| "Too many live outs".to_owned())?;
+        self.live_outs.push(live_out);
+        self.num_live_outs = num_live_outs;
+        Ok(())
+    }
+
+    /// Recompute `num_locations` and `num_live_outs` from the content of the record.
+    pub fn sync_counts(&mut self) -> Result<(), String> {
+        (self.num_locations, self.num_live_outs) = self.counts()?;
+        Ok(())
+    }
+
+    /// The `num_locations` and `num_live_outs` values matching the content of the record.
+    fn counts(&self) -> Result<(u16, u16), String> {
+        let num_locations = u16::try_from(self.locations.len())
+            .map_err(|_| format!("Too many locations: {}", self.locations.len()))?;
+        let num_live_outs = u16::try_from(self.live_outs.len())
+            .map_err(|_| format!("Too many live outs: {}", self.live_outs.len()))?;
+        Ok((num_locations, num_live_outs))
+    }
+
+    fn parse(data: &mut Bytes, stream_offset: &mut usize) -> Result<StkMapRecord, ParsingError> {
         let mut sm: StkMapRecord = StkMapRecord::default();
         let old_len = data.len();
         sm.patch_point_id = u64::drain_from_bytes(data)?;
@@ -354,13 +515,20 @@ impl StackMap {
     /// that contain a stackmap are linked, the corresponding stackmaps are concatinated.
     /// Thus, this function might return more than one stackmap.
     pub fn new(data: &mut Vec<u8>) -> Result<Vec<StackMap>, ParsingError> {
+        let maps = StackMap::parse_with_offsets(data)?;
+        Ok(maps.into_iter().map(|(_, map)| map).collect())
+    }
+
+    /// Same as `new`, but each stackmap is returned together with its offset in `data`.
+    fn parse_with_offsets(data: &[u8]) -> Result<Vec<(usize, StackMap)>, ParsingError> {
         let mut result = Vec::new();
-        let mut data = Bytes::copy_from_slice(data);
-        while !data.is_empty() {
+        let mut remaining = Bytes::copy_from_slice(data);
+        while !remaining.is_empty() {
+            let offset = data.len() - remaining.len();
             // The `parse` call will raise an error if parsing fails, hence this will
             // not end in a endless loop.
-            let map = StackMap::parse(&mut data)?;
-            result.push(map);
+            let map = StackMap::parse(&mut remaining)?;
+            result.push((offset, map));
         }
         Ok(result)
     }
@@ -393,162 +561,722 @@ impl StackMap {
 
         let mut stream_offset = start_size - data.len();
         for _ in 0..stack_map.num_records {
-            let record = StkMapRecord::new(data, &mut stream_offset)?;
+            let record = StkMapRecord::parse(data, &mut stream_offset)?;
             stack_map.stk_map_records.push(record);
         }
 
         Ok(stack_map)
     }
 
+    /// The LLVM stackmap version of this stackmap.
+    pub fn version(&self) -> u8 {
+        self.header.version
+    }
+
+    /// The functions described by this stackmap.
+    pub fn functions(&self) -> &[StkSizeRecord] {
+        &self.stk_size_records
+    }
+
+    /// The large constants referenced by ConstIndex locations.
+    pub fn constants(&self) -> &[Constant] {
+        &self.large_constants
+    }
+
+    /// The records of all functions.
+    pub fn records(&self) -> &[StkMapRecord] {
+        &self.stk_map_records
+    }
+
+    /// Iterate over all functions together with the records that belong to them.
+    /// If the `record_count` values are inconsistent with the number of records,
+    /// the records of the trailing functions are truncated.
+    pub fn function_records(&self) -> impl Iterator<Item = (&StkSizeRecord, &[StkMapRecord])> {
+        let mut start = 0usize;
+        self.stk_size_records.iter().map(move |function| {
+            let count = usize::try_from(function.record_count).unwrap_or(usize::MAX);
+            let end = start.saturating_add(count).min(self.stk_map_records.len());
+            let records = &self.stk_map_records[start..end];
+            start = end;
+            (function, records)
+        })
+    }
+
+    /// The number of functions, derived from the functions actually present.
+    /// Use `num_functions` to get the value stored in the stackmap.
+    pub fn function_count(&self) -> usize {
+        self.stk_size_records.len()
+    }
+
+    /// The number of constants, derived from the constants actually present.
+    /// Use `num_constants` to get the value stored in the stackmap.
+    pub fn constant_count(&self) -> usize {
+        self.large_constants.len()
+    }
+
+    /// The number of records, derived from the records actually present.
+    /// Use `num_records` to get the value stored in the stackmap.
+    pub fn record_count(&self) -> usize {
+        self.stk_map_records.len()
+    }
+
+    /// Append a function together with its `records` while keeping `num_functions`,
+    /// `num_records` and the `record_count` of `function` in sync.
+    pub fn push_function(
+        &mut self,
+        mut function: StkSizeRecord,
+        mut records: Vec<StkMapRecord>,
+    ) -> Result<(), String> {
+        let num_functions = u32::try_from(self.stk_size_records.len() + 1)
+            .map_err(|_| "Too many functions".to_owned())?;
+        let num_records = self
+            .stk_map_records
+            .len()
+            .checked_add(records.len())
+            .and_then(|len| u32::try_from(len).ok())
+            .ok_or_else(|| "Too many records".to_owned())?;
+        // Validate all records before anything is modified.
+        let record_counts = records
+            .iter()
+            .map(StkMapRecord::counts)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (record, counts) in records.iter_mut().zip(record_counts) {
+            (record.num_locations, record.num_live_outs) = counts;
+        }
+        function.record_count = records.len() as u64;
+        self.stk_size_records.push(function);
+        self.stk_map_records.append(&mut records);
+        self.num_functions = num_functions;
+        self.num_records = num_records;
+        Ok(())
+    }
+
+    /// Append `constant` while keeping `num_constants` in sync. Returns the index
+    /// that must be used by a ConstIndex location to refer to the constant.
+    pub fn push_constant(&mut self, constant: Constant) -> Result<i32, String> {
+        let idx = i32::try_from(self.large_constants.len())
+            .map_err(|_| "Too many constants".to_owned())?;
+        self.large_constants.push(constant);
+        self.num_constants = (idx + 1) as u32;
+        Ok(idx)
+    }
+
+    /// Recompute all `num_*` fields of the stackmap and its records from their content.
+    /// The `record_count` of the functions is left untouched, since records can not be
+    /// attributed to functions without it.
+    pub fn sync_counts(&mut self) -> Result<(), String> {
+        let num_functions = u32::try_from(self.stk_size_records.len())
+            .map_err(|_| format!("Too many functions: {}", self.stk_size_records.len()))?;
+        let num_constants = u32::try_from(self.large_constants.len())
+            .map_err(|_| format!("Too many constants: {}", self.large_constants.len()))?;
+        let num_records = u32::try_from(self.stk_map_records.len())
+            .map_err(|_| format!("Too many records: {}", self.stk_map_records.len()))?;
+        let record_counts = self
+            .stk_map_records
+            .iter()
+            .map(StkMapRecord::counts)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.num_functions = num_functions;
+        self.num_constants = num_constants;
+        self.num_records = num_records;
+        for (record, counts) in self.stk_map_records.iter_mut().zip(record_counts) {
+            (record.num_locations, record.num_live_outs) = counts;
+        }
+        Ok(())
+    }
+
     /// Get the byte range of the binary that contains the bytes of section `section_name`.
     /// Thus `file_bytes[range.start..range.end]` yields the content of the section.
     /// If the ELF does not contain a section with the given `section_name`, None is returned.
     #[cfg(feature = "from-elf")]
-    fn get_section_byte_range(elf: &Elf, section_name: &str) -> Option<Range<usize>> {
-        let section_headers = &elf.section_headers;
-        // Indices into the section header string table.
-        let section_name_ids = section_headers
-            .iter()
-            .map(|section| section.sh_name)
-            .collect::<Vec<_>>();
+    pub(crate) fn get_section_byte_range(elf: &Elf, section_name: &str) -> Option<Range<usize>> {
+        StackMap::get_section_header(elf, section_name).and_then(|section| section.file_range())
+    }
 
-        for (idx, offset) in section_name_ids.into_iter().enumerate() {
-            if elf
-                .shdr_strtab
-                .get_at(offset)
+    /// Get the header of the section named `section_name`.
+    #[cfg(feature = "from-elf")]
+    pub(crate) fn get_section_header<'a>(
+        elf: &'a Elf,
+        section_name: &str,
+    ) -> Option<&'a elf::SectionHeader> {
+        elf.section_headers.iter().find(|section| {
+            elf.shdr_strtab
+                .get_at(section.sh_name)
                 .map_or(false, |e| e == section_name)
-            {
-                // Index must be valid since we generated section_name_ids by mapping
-                // the entries of elf.section_headers.
-                return section_headers.get(idx).unwrap().file_range();
+        })
+    }
+
+    /// Get the content of the section `section_name`, which is transparently
+    /// decompressed if it is compressed (SHF_COMPRESSED). `bytes` must be the file
+    /// `elf` was parsed from. Returns None if there is no such section.
+    #[cfg(feature = "from-elf")]
+    pub(crate) fn read_section<'a>(
+        elf: &Elf,
+        bytes: &'a [u8],
+        section_name: &str,
+    ) -> Result<Option<Cow<'a, [u8]>>, ParsingError> {
+        let section = match StackMap::get_section_header(elf, section_name) {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+        let data = match section.file_range() {
+            Some(range) => bytes.get(range).ok_or_else(|| {
+                ParsingError::Malformed(format!("Section {} is out of bounds", section_name))
+            })?,
+            // SHT_NOBITS
+            None => return Ok(None),
+        };
+
+        if section.sh_flags & elf::section_header::SHF_COMPRESSED as u64 == 0 {
+            return Ok(Some(Cow::Borrowed(data)));
+        }
+        StackMap::decompress_section(elf, data).map(|data| Some(Cow::Owned(data)))
+    }
+
+    /// Decompress the content `data` of a section with the SHF_COMPRESSED flag.
+    #[cfg(feature = "compressed-sections")]
+    fn decompress_section(elf: &Elf, data: &[u8]) -> Result<Vec<u8>, ParsingError> {
+        use goblin::container::{Container, Ctx, Endian};
+        use goblin::elf::compression_header::{
+            CompressionHeader, ELFCOMPRESS_ZLIB, ELFCOMPRESS_ZSTD,
+        };
+        use std::io::Read;
+
+        let ctx = Ctx::new(
+            if elf.is_64 {
+                Container::Big
+            } else {
+                Container::Little
+            },
+            if elf.little_endian {
+                Endian::Little
+            } else {
+                Endian::Big
+            },
+        );
+        let header = CompressionHeader::parse(data, 0, ctx)?;
+        let compressed = &data[CompressionHeader::size(ctx).min(data.len())..];
+        let size = header.ch_size as usize;
+
+        let decompressed = match header.ch_type {
+            ELFCOMPRESS_ZLIB => {
+                miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(compressed, size).map_err(
+                    |err| ParsingError::Malformed(format!("Failed to decompress section: {}", err)),
+                )?
+            }
+            ELFCOMPRESS_ZSTD => {
+                let mut input = compressed;
+                let decoder =
+                    ruzstd::decoding::StreamingDecoder::new(&mut input).map_err(|err| {
+                        ParsingError::Malformed(format!("Failed to decompress section: {}", err))
+                    })?;
+                // `size` is not trusted, thus the buffer grows with the actual content.
+                let mut decompressed = Vec::new();
+                decoder.take(size as u64).read_to_end(&mut decompressed)?;
+                decompressed
+            }
+            ch_type => {
+                return Err(ParsingError::Malformed(format!(
+                    "Unsupported section compression type: {}",
+                    ch_type
+                )))
             }
+        };
+
+        if decompressed.len() != size {
+            return Err(ParsingError::Malformed(
+                "Decompressed section size does not match the compression header".to_owned(),
+            ));
         }
-        None
+        Ok(decompressed)
+    }
+
+    #[cfg(all(feature = "from-elf", not(feature = "compressed-sections")))]
+    fn decompress_section(_elf: &Elf, _data: &[u8]) -> Result<Vec<u8>, ParsingError> {
+        Err(ParsingError::Malformed(
+            "Compressed sections require the compressed-sections feature".to_owned(),
+        ))
     }
 
     /// Relocates the function addresses contained in the stack map section.
+    /// `stack_map_section_range` is the range of virtual addresses the (uncompressed)
+    /// section is mapped to. Addresses are relocated as if the binary is loaded at
+    /// address zero.
     #[cfg(feature = "from-elf")]
     fn relocate_stackmap_section(
         elf: &Elf,
         stack_map_section_range: Range<usize>,
         stack_map_section: &mut [u8],
     ) -> Result<(), ParsingError> {
-        let relas = &elf.dynrelas;
-        for rela in relas.iter() {
-            let file_offset = rela.r_offset as usize;
+        for reloc in elf.dynrelas.iter().chain(elf.dynrels.iter()) {
+            let address = reloc.r_offset as usize;
 
             // Skip relocs for other sections then the stack map.
-            if !stack_map_section_range.contains(&file_offset) {
+            if !stack_map_section_range.contains(&address) {
                 continue;
             }
 
-            let offset = file_offset - stack_map_section_range.start;
-            match rela.r_type {
-                elf::reloc::R_X86_64_RELATIVE => {
-                    let val = rela.r_addend.unwrap().to_ne_bytes().to_vec();
-                    stack_map_section[offset..(offset + 8)].copy_from_slice(&val);
-                }
-                elf::reloc::R_X86_64_64 => {
-                    let dynsym_idx = rela.r_sym;
-                    let sym = elf.dynsyms.get(dynsym_idx);
-                    let sym_val = sym
-                        .ok_or(ParsingError::Malformed(
-                            "Failed to get symbol for relocation from dynsmy".to_owned(),
-                        ))
-                        .map(|s| s.st_value)?;
-                    let sym_val = sym_val.to_ne_bytes().to_vec();
-                    stack_map_section[offset..(offset + 8)].copy_from_slice(&sym_val);
+            let offset = address - stack_map_section_range.start;
+            let slot = stack_map_section
+                .get_mut(offset..offset + 8)
+                .ok_or_else(|| {
+                    ParsingError::Malformed("Relocation exceeds the stack map section".to_owned())
+                })?;
+            // REL relocations store the addend at the relocated location.
+            let addend = match reloc.r_addend {
+                Some(addend) => addend as u64,
+                None => u64::from_ne_bytes(slot[..].try_into().unwrap()),
+            };
+            let symbol_value = || {
+                elf.dynsyms
+                    .get(reloc.r_sym)
+                    .map(|sym| sym.st_value)
+                    .ok_or_else(|| {
+                        ParsingError::Malformed(
+                            "Failed to get symbol for relocation from dynsym".to_owned(),
+                        )
+                    })
+            };
+
+            let value = match (elf.header.e_machine, reloc.r_type) {
+                (elf::header::EM_X86_64, elf::reloc::R_X86_64_RELATIVE)
+                | (elf::header::EM_AARCH64, elf::reloc::R_AARCH64_RELATIVE) => addend,
+                (elf::header::EM_X86_64, elf::reloc::R_X86_64_64)
+                | (elf::header::EM_AARCH64, elf::reloc::R_AARCH64_ABS64) => {
+                    symbol_value()?.wrapping_add(addend)
                 }
-                _ => {
-                    if stack_map_section_range.contains(&offset) {
-                        todo!("Unsupported relocation for stack map: {:#?}", rela);
-                    }
+                (_, r_type) => {
+                    return Err(ParsingError::Malformed(format!(
+                        "Unsupported relocation for stack map: {}",
+                        r_type
+                    )))
                 }
-            }
+            };
+            slot.copy_from_slice(&value.to_ne_bytes());
         }
         Ok(())
     }
 
-    /// Check whether `path` points to a binary that contains a stackmap.
-    /// This will also return false if the path does not exist.
+    /// Read the ELF header and the section headers from `reader` without reading
+    /// the remaining content of the file.
     #[cfg(feature = "from-elf")]
-    pub fn has_stackmap<T: AsRef<Path>>(path: T) -> bool {
-        let bytes = fs::read(path);
-        if let Ok(bytes) = bytes {
-            let elf = Elf::parse(&bytes);
-            return elf.map_or(false, |elf| {
-                StackMap::get_section_byte_range(&elf, ".llvm_stackmaps").is_some()
+    pub(crate) fn read_elf_headers<R: Read + Seek>(
+        reader: &mut R,
+    ) -> Result<ElfHeaders, ParsingError> {
+        let mut header_bytes = Vec::with_capacity(elf::header::header64::SIZEOF_EHDR);
+        reader
+            .by_ref()
+            .take(elf::header::header64::SIZEOF_EHDR as u64)
+            .read_to_end(&mut header_bytes)?;
+        let header = Elf::parse_header(&header_bytes)?;
+        let ctx = goblin::container::Ctx::new(header.container()?, header.endianness()?);
+        if header.e_shoff == 0 {
+            return Ok(ElfHeaders {
+                header,
+                section_headers: Vec::new(),
+                shstrndx: 0,
             });
         }
-        false
+
+        // The section headers must fit into the file, such that corrupt counts do
+        // not cause huge allocations.
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let read_section_headers = |reader: &mut R, count: usize| {
+            let table_size = count
+                .checked_mul(elf::SectionHeader::size(ctx))
+                .filter(|&size| header.e_shoff.checked_add(size as u64) <= Some(file_len))
+                .ok_or_else(|| {
+                    ParsingError::Malformed("Section headers exceed the file".to_owned())
+                })?;
+            let mut table = vec![0; table_size];
+            reader.seek(SeekFrom::Start(header.e_shoff))?;
+            reader.read_exact(&mut table)?;
+            Ok::<_, ParsingError>(elf::SectionHeader::parse_from(&table, 0, count, ctx)?)
+        };
+
+        // If there are too many sections for the ELF header, their number and the
+        // index of the string table are stored in the first section header.
+        let mut count = header.e_shnum as usize;
+        let mut shstrndx = header.e_shstrndx as usize;
+        if count == 0 || shstrndx == elf::section_header::SHN_XINDEX as usize {
+            let first = read_section_headers(reader, 1)?.remove(0);
+            if count == 0 {
+                count = first.sh_size as usize;
+            }
+            if shstrndx == elf::section_header::SHN_XINDEX as usize {
+                shstrndx = first.sh_link as usize;
+            }
+        }
+
+        Ok(ElfHeaders {
+            header,
+            section_headers: read_section_headers(reader, count)?,
+            shstrndx,
+        })
+    }
+
+    /// Check whether `path` points to a binary that contains a stackmap.
+    /// This will also return false if the path does not exist. Only the headers
+    /// of the binary are read.
+    #[cfg(feature = "from-elf")]
+    pub fn has_stackmap<T: AsRef<Path>>(path: T) -> bool {
+        let options = ParseOptions::default();
+        let has_stackmap = || -> Result<bool, ParsingError> {
+            let mut file = fs::File::open(path)?;
+            let file_len = file.metadata()?.len();
+            let headers = StackMap::read_elf_headers(&mut file)?;
+            let shstrtab = match headers.section_headers.get(headers.shstrndx) {
+                Some(shstrtab) => shstrtab,
+                None => return Ok(false),
+            };
+            if shstrtab.sh_offset.checked_add(shstrtab.sh_size) > Some(file_len) {
+                return Ok(false);
+            }
+
+            let mut names = vec![0; shstrtab.sh_size as usize];
+            file.seek(SeekFrom::Start(shstrtab.sh_offset))?;
+            file.read_exact(&mut names)?;
+            let names = Strtab::parse(&names, 0, names.len(), 0)?;
+            Ok(headers.section_headers.iter().any(|section| {
+                names.get_at(section.sh_name).map_or(false, |name| {
+                    options.section_names.iter().any(|n| n == name)
+                })
+            }))
+        };
+        has_stackmap().unwrap_or(false)
     }
 
-    /// Parse the stackmap(s) of the binary `path` points to.
+    /// Parse the stackmap(s) of the binary `path` points to. The functions of
+    /// the returned stackmaps are symbolized using the symbols of the binary.
+    ///
+    /// For relocatable objects, the function addresses are offsets into the
+    /// sections containing the functions, and the functions are named after the
+    /// symbols the relocations of the stackmap section refer to.
     #[cfg(feature = "from-elf")]
     pub fn from_path<T: AsRef<Path>>(path: T) -> Result<Vec<StackMap>, ParsingError> {
+        StackMap::from_path_with_options(path, &ParseOptions::default())
+    }
 
+    /// Same as `from_path`, but the stackmap section is searched as configured by `options`.
+    #[cfg(feature = "from-elf")]
+    pub fn from_path_with_options<T: AsRef<Path>>(
+        path: T,
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let bytes = fs::read(path)?;
+        StackMap::from_elf_bytes(&bytes, options)
+    }
+
+    /// Same as `from_path`, but if the binary is stripped, the functions are
+    /// symbolized using the symbols of the separate debug file found by `locator`
+    /// (see `Symbols::from_path_with`).
+    #[cfg(feature = "from-elf")]
+    pub fn from_path_with<T: AsRef<Path>>(
+        path: T,
+        locator: &DebugFileLocator,
+    ) -> Result<Vec<StackMap>, ParsingError> {
         let bytes = fs::read(path.as_ref())?;
         let elf = Elf::parse(&bytes)?;
+        let mut maps = StackMap::from_elf(&elf, &bytes, &ParseOptions::default())?;
+        // The functions of relocatable objects are named while relocating.
+        if elf.header.e_type != elf::header::ET_REL {
+            let symbols = Symbols::from_elf_with(path.as_ref(), &elf, &bytes, locator);
+            for map in maps.iter_mut() {
+                map.symbolize(&symbols);
+            }
+        }
+        Ok(maps)
+    }
+
+    /// Parse the stackmap(s) of the ELF file `bytes`. The functions of the returned
+    /// stackmaps are symbolized using the symbols of the binary.
+    #[cfg(feature = "from-elf")]
+    pub fn from_elf_bytes(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let elf = Elf::parse(bytes)?;
+        let mut maps = StackMap::from_elf(&elf, bytes, options)?;
+        // The functions of relocatable objects are named while relocating.
+        if elf.header.e_type != elf::header::ET_REL {
+            let symbols = Symbols::from_elf(&elf);
+            for map in maps.iter_mut() {
+                map.symbolize(&symbols);
+            }
+        }
+        Ok(maps)
+    }
 
-        let stackmap_byte_range = StackMap::get_section_byte_range(&elf, ".llvm_stackmaps");
-        if let Some(section_range) = stackmap_byte_range {
-            let mut bytes = fs::read(path)?;
-            let section_bytes = &mut bytes[section_range.clone()];
-            StackMap::relocate_stackmap_section(&elf, section_range, section_bytes)?;
-            return Ok(StackMap::new(&mut section_bytes.to_owned())?);
+    /// Parse and relocate the (unsymbolized) stackmap(s) of `elf`, which must be
+    /// parsed from `bytes`.
+    #[cfg(feature = "from-elf")]
+    pub(crate) fn from_elf(
+        elf: &Elf,
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        for section_name in options.section_names.iter() {
+            let section = match StackMap::read_section(elf, bytes, section_name)? {
+                Some(section) => section,
+                None => continue,
+            };
+            // Unwrap is fine since the section was found above.
+            let address = StackMap::get_section_header(elf, section_name)
+                .unwrap()
+                .sh_addr as usize;
+
+            let mut section_bytes = section.into_owned();
+            if elf.header.e_type == elf::header::ET_REL {
+                return StackMap::from_object_section(elf, section_name, &mut section_bytes);
+            }
+            let address_range = address..address.saturating_add(section_bytes.len());
+            StackMap::relocate_stackmap_section(elf, address_range, &mut section_bytes)?;
+            return StackMap::new(&mut section_bytes);
         }
         Err(ParsingError::StackMapSectionNotFound)
     }
 
+    /// Parse the stackmap section `section_name` of the relocatable object `elf`.
+    /// Since the functions are not yet placed by the linker, their addresses are
+    /// offsets into the section containing them, and their names are taken from
+    /// the symbols the relocations of the section refer to.
+    #[cfg(feature = "from-elf")]
+    fn from_object_section(
+        elf: &Elf,
+        section_name: &str,
+        section_bytes: &mut [u8],
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        // Unwrap is fine since the caller found the section.
+        let section_idx = elf
+            .section_headers
+            .iter()
+            .position(|section| elf.shdr_strtab.get_at(section.sh_name) == Some(section_name))
+            .unwrap();
+        let names = StackMap::relocate_object_stackmap_section(elf, section_idx, section_bytes)?;
+        StackMap::from_relocated_object_section(section_bytes, &names)
+    }
+
+    /// Parse the relocated stackmap section `section_bytes` of a relocatable object.
+    /// The functions are named after `names`, the names of the symbols referred to
+    /// by the relocations of the section indexed by the offset of the relocation.
+    #[cfg(any(feature = "from-elf", feature = "from-pe"))]
+    pub(crate) fn from_relocated_object_section(
+        section_bytes: &[u8],
+        names: &std::collections::HashMap<usize, String>,
+    ) -> Result<Vec<StackMap>, ParsingError> {
+        let mut maps = Vec::new();
+        for (map_offset, mut map) in StackMap::parse_with_offsets(section_bytes)? {
+            // Functions placed into different sections might share the same offset,
+            // in which case only one of their names is kept.
+            let symbols: Symbols = map
+                .stk_size_records
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, function)| {
+                    // The header and the counts take 16 bytes, each function 24 bytes.
+                    let offset = map_offset + 16 + 24 * idx;
+                    names.get(&offset).map(|name| crate::Symbol {
+                        name: name.clone(),
+                        address: function.function_address,
+                        size: 0,
+                    })
+                })
+                .collect();
+            map.symbolize(&symbols);
+            maps.push(map);
+        }
+        Ok(maps)
+    }
+
+    /// Applies the relocations of the stack map section of a relocatable object and
+    /// returns the names of the relocated symbols indexed by the offset of the
+    /// relocation. `section_idx` is the index of the section header of the section.
+    #[cfg(feature = "from-elf")]
+    fn relocate_object_stackmap_section(
+        elf: &Elf,
+        section_idx: usize,
+        stack_map_section: &mut [u8],
+    ) -> Result<HashMap<usize, String>, ParsingError> {
+        let mut names = HashMap::new();
+        for (reloc_section_idx, relocs) in elf.shdr_relocs.iter() {
+            let target = elf
+                .section_headers
+                .get(*reloc_section_idx)
+                .map(|section| section.sh_info as usize);
+            // Skip relocs for other sections then the stack map.
+            if target != Some(section_idx) {
+                continue;
+            }
+
+            for reloc in relocs.iter() {
+                match (elf.header.e_machine, reloc.r_type) {
+                    (elf::header::EM_X86_64, elf::reloc::R_X86_64_64) => (),
+                    (elf::header::EM_AARCH64, elf::reloc::R_AARCH64_ABS64) => (),
+                    (_, r_type) => {
+                        return Err(ParsingError::Malformed(format!(
+                            "Unsupported relocation for stack map: {}",
+                            r_type
+                        )))
+                    }
+                }
+
+                let offset = reloc.r_offset as usize;
+                let slot = stack_map_section
+                    .get_mut(offset..offset.saturating_add(8))
+                    .ok_or_else(|| {
+                        ParsingError::Malformed(
+                            "Relocation exceeds the stack map section".to_owned(),
+                        )
+                    })?;
+                let sym = elf.syms.get(reloc.r_sym).ok_or_else(|| {
+                    ParsingError::Malformed("Failed to get symbol for relocation".to_owned())
+                })?;
+                // REL relocations store the addend at the relocated location.
+                let addend = match reloc.r_addend {
+                    Some(addend) => addend as u64,
+                    None => u64::from_ne_bytes(slot[..].try_into().unwrap()),
+                };
+                let address = sym.st_value.wrapping_add(addend);
+                slot.copy_from_slice(&address.to_ne_bytes());
+
+                // Static functions might be referenced relative to their section.
+                let name = if sym.st_type() == elf::sym::STT_SECTION {
+                    elf.syms
+                        .iter()
+                        .find(|s| {
+                            s.is_function() && s.st_shndx == sym.st_shndx && s.st_value == address
+                        })
+                        .and_then(|s| elf.strtab.get_at(s.st_name))
+                } else {
+                    elf.strtab.get_at(sym.st_name)
+                };
+                if let Some(name) = name.filter(|name| !name.is_empty()) {
+                    names.insert(offset, name.to_owned());
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Write the stackmap to `out` using the same notation as llvm-readobj --stackmap.
+    pub fn write_readobj(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "{}", self)
+    }
+
     /// Pretty print the stackmap using the same notation as llvm-readobj --stackmap.
     pub fn pretty_print(&self) -> () {
-        println!("LLVM StackMap Version: {}", self.header.version);
-        println!("Num Functions: {}", self.num_functions);
-        for f in &self.stk_size_records {
-            println!(
+        print!("{}", self);
+    }
+}
+
+/// Format `loc` as done by llvm-readobj. If `constants` is given, the value a
+/// ConstIndex location refers to is resolved and printed as well.
+fn fmt_location(
+    f: &mut fmt::Formatter<'_>,
+    loc: &Location,
+    constants: Option<&[Constant]>,
+) -> fmt::Result {
+    match loc.loc_type {
+        LocationType::Register => write!(f, "Register R#{}", loc.dwarf_regnum as u32)?,
+        LocationType::Direct => write!(
+            f,
+            "Direct R#{} + {}",
+            loc.dwarf_regnum as u32, loc.offset_or_constant
+        )?,
+        LocationType::Indirect => write!(
+            f,
+            "Indirect [ R#{} + {}]",
+            loc.dwarf_regnum as u32, loc.offset_or_constant
+        )?,
+        // llvm-readobj prints small constants as unsigned 32 bit values.
+        LocationType::Constant => write!(f, "Constant {}", loc.offset_or_constant as u32)?,
+        LocationType::ConstIndex => {
+            write!(f, "ConstantIndex #{}", loc.offset_or_constant)?;
+            if let Some(constants) = constants {
+                let constant = usize::try_from(loc.offset_or_constant)
+                    .ok()
+                    .and_then(|idx| constants.get(idx));
+                match constant {
+                    Some(constant) => write!(f, " ({})", constant)?,
+                    None => write!(f, " (<out of range>)")?,
+                }
+            }
+        }
+        LocationType::Invalid => write!(
+            f,
+            "<invalid location> R#{} + {}",
+            loc.dwarf_regnum as u32, loc.offset_or_constant
+        )?,
+    }
+    write!(f, ", size: {}", loc.loc_size)
+}
+
+/// Format `record` as done by llvm-readobj (see `fmt_location` for `constants`).
+fn fmt_record(
+    f: &mut fmt::Formatter<'_>,
+    record: &StkMapRecord,
+    constants: Option<&[Constant]>,
+) -> fmt::Result {
+    writeln!(
+        f,
+        "  Record ID: {}, instruction offset: {}",
+        record.patch_point_id, record.instruction_offset
+    )?;
+    writeln!(f, "    {} locations:", record.num_locations)?;
+    for (i, l) in record.locations.iter().enumerate() {
+        write!(f, "      #{}: ", i + 1)?;
+        fmt_location(f, l, constants)?;
+        writeln!(f)?;
+    }
+    write!(f, "    {} live-outs: [ ", record.num_live_outs)?;
+    for lo in record.live_outs.iter() {
+        write!(f, "{} ", lo)?;
+    }
+    writeln!(f, "]")
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_location(f, self, None)
+    }
+}
+
+impl fmt::Display for LiveOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "R#{} ({}-bytes)", self.dwarf_regnum as u32, self.size)
+    }
+}
+
+/// Since a record does not know the constants of its stackmap, ConstIndex locations
+/// are printed without the value they refer to.
+impl fmt::Display for StkMapRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_record(f, self, None)
+    }
+}
+
+/// Formats the stackmap using the same notation as llvm-readobj --stackmap.
+impl fmt::Display for StackMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "LLVM StackMap Version: {}", self.header.version)?;
+        writeln!(f, "Num Functions: {}", self.num_functions)?;
+        for func in &self.stk_size_records {
+            writeln!(
+                f,
                 "  Function address: {}, stack size: {}, callsite record count: {}",
-                f.function_address, f.stack_size, f.record_count
-            );
+                func.function_address, func.stack_size, func.record_count
+            )?;
         }
-        println!("Num Constants: {}", self.num_constants);
+        writeln!(f, "Num Constants: {}", self.num_constants)?;
         for (i, c) in self.large_constants.iter().enumerate() {
-            println!("  #{}: {}", i + 1, c);
+            writeln!(f, "  #{}: {}", i + 1, c)?;
         }
-        println!("Num Records: {}", self.num_records);
+        writeln!(f, "Num Records: {}", self.num_records)?;
         for r in self.stk_map_records.iter() {
-            println!(
-                "  Record ID: {}, instruction offset: {}",
-                r.patch_point_id, r.instruction_offset
-            );
-            println!("    {} locations:", r.num_locations);
-            for (i, l) in r.locations.iter().enumerate() {
-                let type_str = match l.loc_type {
-                    LocationType::Register => format!("Register R#{}", l.dwarf_regnum as u32),
-                    LocationType::Direct => format!(
-                        "Direct R#{} + {}",
-                        l.dwarf_regnum as u32, l.offset_or_constant
-                    ),
-                    LocationType::Indirect => format!(
-                        "Indirect [ R#{} + {}]",
-                        l.dwarf_regnum as u32, l.offset_or_constant
-                    ),
-                    LocationType::Constant => format!("Constant {}", l.offset_or_constant),
-                    LocationType::ConstIndex => format!(
-                        "ConstantIndex #{} ({})",
-                        l.offset_or_constant, self.large_constants[l.offset_or_constant as usize]
-                    ),
-                    _ => {
-                        unreachable!("Unexpected Location type");
-                    }
-                };
-                println!("      #{}: {}, size: {}", i + 1, type_str, l.loc_size);
-            }
-            let live_out_str: String = r
-                .live_outs
-                .iter()
-                .map(|lo| format!("R#{} ({}-bytes) ", lo.dwarf_regnum as u32, lo.size))
-                .collect();
-            println!("    {} live-outs: [ {}]", r.num_live_outs, live_out_str);
+            fmt_record(f, r, Some(&self.large_constants))?;
         }
+        Ok(())
     }
 }
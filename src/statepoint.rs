@@ -0,0 +1,178 @@
+use std::fmt;
+
+use crate::{Location, LocationType, StkMapRecord};
+
+/// Number of constant locations that precede the deopt locations of a statepoint.
+const NUM_HEADER_LOCATIONS: usize = 3;
+
+/// The statepoint is a GC transition (`StatepointFlags::GCTransition` in LLVM).
+pub const STATEPOINT_FLAG_GC_TRANSITION: u64 = 1;
+/// The deopt values are live-in only (`StatepointFlags::DeoptLiveIn` in LLVM).
+pub const STATEPOINT_FLAG_DEOPT_LIVE_IN: u64 = 2;
+
+/// Reasons why a record does not conform to the layout of a statepoint record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatepointError {
+    /// The record has less locations than the leading constants of a statepoint.
+    MissingHeader(usize),
+    /// The leading location with the given index is not a constant.
+    NonConstantHeader(usize),
+    /// The number of deopt locations exceeds the number of remaining locations.
+    DeoptLocationsOutOfBounds {
+        num_deopt: i32,
+        num_remaining: usize,
+    },
+    /// The locations following the deopt locations do not form (base, derived) pairs.
+    UnpairedGcPointer,
+}
+
+impl fmt::Display for StatepointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatepointError::MissingHeader(num_locations) => write!(
+                f,
+                "statepoint records require at least {} locations, but only {} exist",
+                NUM_HEADER_LOCATIONS, num_locations
+            ),
+            StatepointError::NonConstantHeader(idx) => {
+                write!(
+                    f,
+                    "location #{} of a statepoint must be a constant",
+                    idx + 1
+                )
+            }
+            StatepointError::DeoptLocationsOutOfBounds {
+                num_deopt,
+                num_remaining,
+            } => write!(
+                f,
+                "{} deopt locations are declared, but only {} locations remain",
+                num_deopt, num_remaining
+            ),
+            StatepointError::UnpairedGcPointer => {
+                write!(f, "GC pointers do not form (base, derived) pairs")
+            }
+        }
+    }
+}
+
+/// The locations of a GC pointer that must be relocated at a statepoint. `derived`
+/// points into (or is equal to) the object `base` points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcPointerPair<'a> {
+    pub base: &'a Location,
+    pub derived: &'a Location,
+}
+
+/// A view on a record emitted for a `gc.statepoint`. The locations of such records
+/// follow a fixed layout:
+///
+/// 1. Constant: The calling convention of the call target.
+/// 2. Constant: The flags passed to the statepoint intrinsic.
+/// 3. Constant: The number of deopt locations that follow.
+/// 4. The locations of the values of the `deopt` operand bundle.
+/// 5. The remaining locations are (base, derived) pairs of GC pointers.
+#[derive(Debug, Clone, Copy)]
+pub struct StatepointRecord<'a> {
+    record: &'a StkMapRecord,
+    calling_convention: u64,
+    flags: u64,
+    num_deopt: usize,
+}
+
+impl<'a> StatepointRecord<'a> {
+    /// Interpret `record` as a statepoint record. Fails if the locations of
+    /// `record` do not follow the layout of statepoint records.
+    pub fn new(record: &'a StkMapRecord) -> Result<StatepointRecord<'a>, StatepointError> {
+        let locations = record.locations();
+        if locations.len() < NUM_HEADER_LOCATIONS {
+            return Err(StatepointError::MissingHeader(locations.len()));
+        }
+
+        let constant = |idx: usize| -> Result<i32, StatepointError> {
+            let loc = &locations[idx];
+            match loc.loc_type {
+                LocationType::Constant => Ok(loc.offset_or_constant),
+                _ => Err(StatepointError::NonConstantHeader(idx)),
+            }
+        };
+        let calling_convention = constant(0)? as u32 as u64;
+        let flags = constant(1)? as u32 as u64;
+        let num_deopt = constant(2)?;
+
+        let num_remaining = locations.len() - NUM_HEADER_LOCATIONS;
+        let num_deopt = usize::try_from(num_deopt)
+            .ok()
+            .filter(|num_deopt| *num_deopt <= num_remaining)
+            .ok_or(StatepointError::DeoptLocationsOutOfBounds {
+                num_deopt,
+                num_remaining,
+            })?;
+        if (num_remaining - num_deopt) % 2 != 0 {
+            return Err(StatepointError::UnpairedGcPointer);
+        }
+
+        Ok(StatepointRecord {
+            record,
+            calling_convention,
+            flags,
+            num_deopt,
+        })
+    }
+
+    /// The underlying record.
+    pub fn record(&self) -> &'a StkMapRecord {
+        self.record
+    }
+
+    /// The ID passed to the statepoint intrinsic.
+    pub fn id(&self) -> u64 {
+        self.record.patch_point_id
+    }
+
+    /// The LLVM calling convention ID of the call target (e.g., 0 for `ccc`).
+    pub fn calling_convention(&self) -> u64 {
+        self.calling_convention
+    }
+
+    /// The flags passed to the statepoint intrinsic (see `STATEPOINT_FLAG_*`).
+    pub fn flags(&self) -> u64 {
+        self.flags
+    }
+
+    pub fn is_gc_transition(&self) -> bool {
+        self.flags & STATEPOINT_FLAG_GC_TRANSITION != 0
+    }
+
+    pub fn is_deopt_live_in(&self) -> bool {
+        self.flags & STATEPOINT_FLAG_DEOPT_LIVE_IN != 0
+    }
+
+    /// The locations of the values of the `deopt` operand bundle.
+    pub fn deopt_locations(&self) -> &'a [Location] {
+        let start = NUM_HEADER_LOCATIONS;
+        &self.record.locations()[start..start + self.num_deopt]
+    }
+
+    /// The locations of the GC pointers that must be relocated.
+    pub fn gc_pointer_pairs(&self) -> impl Iterator<Item = GcPointerPair<'a>> {
+        let start = NUM_HEADER_LOCATIONS + self.num_deopt;
+        self.record.locations()[start..]
+            .chunks_exact(2)
+            .map(|pair| GcPointerPair {
+                base: &pair[0],
+                derived: &pair[1],
+            })
+    }
+
+    pub fn gc_pointer_count(&self) -> usize {
+        (self.record.locations().len() - NUM_HEADER_LOCATIONS - self.num_deopt) / 2
+    }
+}
+
+impl StkMapRecord {
+    /// Interpret this record as a statepoint record (see `StatepointRecord`).
+    pub fn as_statepoint(&self) -> Result<StatepointRecord<'_>, StatepointError> {
+        StatepointRecord::new(self)
+    }
+}
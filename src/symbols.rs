@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "from-elf")]
+use {
+    crate::{DebugFileLocator, ParsingError},
+    goblin::elf::{sym, Elf},
+    std::{fs, path::Path},
+};
+
+use crate::{StackMap, StkMapRecord, StkSizeRecord};
+
+/// A function symbol of a binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Symbol {
+    pub name: String,
+    pub address: u64,
+    /// Size of the function in bytes (zero if unknown).
+    pub size: u64,
+}
+
+/// The function symbols of a binary, indexed by their address.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Symbols {
+    by_address: HashMap<u64, Symbol>,
+}
+
+impl Symbols {
+    /// Collect the function symbols of `elf`. Symbols from `.symtab` take precedence
+    /// over those from `.dynsym`, and global symbols over local ones if multiple
+    /// symbols share the same address.
+    #[cfg(feature = "from-elf")]
+    pub fn from_elf(elf: &Elf) -> Symbols {
+        // Address -> (priority, symbol)
+        let mut best: HashMap<u64, (u8, Symbol)> = HashMap::new();
+        let tables = [
+            (&elf.dynsyms, &elf.dynstrtab, 0),
+            (&elf.syms, &elf.strtab, 2),
+        ];
+
+        for (syms, strtab, table_priority) in tables {
+            for s in syms.iter() {
+                if !s.is_function() || s.st_value == 0 {
+                    continue;
+                }
+                let name = match strtab.get_at(s.st_name) {
+                    Some(name) if !name.is_empty() => name,
+                    _ => continue,
+                };
+                let priority = table_priority + (s.st_bind() != sym::STB_LOCAL) as u8;
+                let replace = best
+                    .get(&s.st_value)
+                    .map_or(true, |(current, _)| priority > *current);
+                if replace {
+                    let symbol = Symbol {
+                        name: name.to_owned(),
+                        address: s.st_value,
+                        size: s.st_size,
+                    };
+                    best.insert(s.st_value, (priority, symbol));
+                }
+            }
+        }
+
+        Symbols {
+            by_address: best.into_iter().map(|(k, (_, s))| (k, s)).collect(),
+        }
+    }
+
+    /// Collect the function symbols of the binary at `path`, which must be the file
+    /// `elf` was parsed from. If the binary was stripped, the symbols of its separate
+    /// debug file are added if `locator` is able to find it.
+    #[cfg(feature = "from-elf")]
+    pub fn from_elf_with(
+        path: &Path,
+        elf: &Elf,
+        bytes: &[u8],
+        locator: &DebugFileLocator,
+    ) -> Symbols {
+        let mut symbols = Symbols::from_elf(elf);
+        if !elf.syms.is_empty() {
+            return symbols;
+        }
+
+        let debug_file = locator
+            .find(path, elf, bytes)
+            .and_then(|debug_path| fs::read(debug_path).ok());
+        if let Some(debug_bytes) = debug_file {
+            if let Ok(debug_elf) = Elf::parse(&debug_bytes) {
+                symbols.merge(Symbols::from_elf(&debug_elf));
+            }
+        }
+        symbols
+    }
+
+    /// Same as `from_elf_with`, but read and parse the binary at `path` first.
+    #[cfg(feature = "from-elf")]
+    pub fn from_path_with<T: AsRef<Path>>(
+        path: T,
+        locator: &DebugFileLocator,
+    ) -> Result<Symbols, ParsingError> {
+        let bytes = fs::read(path.as_ref())?;
+        let elf = Elf::parse(&bytes)?;
+        Ok(Symbols::from_elf_with(path.as_ref(), &elf, &bytes, locator))
+    }
+
+    /// Add all symbols of `other`. Symbols of `other` replace those at the same
+    /// address, e.g., the `.symtab` symbols of a separate debug file replace the
+    /// `.dynsym` symbols of the stripped binary.
+    pub fn merge(&mut self, other: Symbols) {
+        self.by_address.extend(other.by_address);
+    }
+
+    /// Add `symbol`, replacing the symbol at the same address if there is one.
+    pub fn insert(&mut self, symbol: Symbol) {
+        self.by_address.insert(symbol.address, symbol);
+    }
+
+    /// Get the function symbol located at `address`.
+    pub fn get(&self, address: u64) -> Option<&Symbol> {
+        self.by_address.get(&address)
+    }
+
+    /// Iterate over all function symbols in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.by_address.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_address.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_address.is_empty()
+    }
+}
+
+impl FromIterator<Symbol> for Symbols {
+    fn from_iter<T: IntoIterator<Item = Symbol>>(iter: T) -> Self {
+        Symbols {
+            by_address: iter.into_iter().map(|s| (s.address, s)).collect(),
+        }
+    }
+}
+
+/// Demangle a Rust or C++ symbol name. Names that are not mangled are returned as is.
+#[cfg(feature = "demangle")]
+pub fn demangle(name: &str) -> String {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return format!("{:#}", demangled);
+    }
+    if let Ok(symbol) = cpp_demangle::Symbol::new(name) {
+        if let Ok(demangled) = symbol.demangle(&Default::default()) {
+            return demangled;
+        }
+    }
+    name.to_owned()
+}
+
+impl StackMap {
+    /// Record the symbols of `symbols` located at the address of a function of
+    /// this stackmap, such that the functions can be referred to by name.
+    pub fn symbolize(&mut self, symbols: &Symbols) {
+        for function in self.stk_size_records.iter() {
+            if let Some(symbol) = symbols.get(function.function_address) {
+                self.symbols.insert(symbol.clone());
+            }
+        }
+    }
+
+    /// The symbols of the functions of this stackmap (see `symbolize`).
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    /// The name of the symbol at the address of `function`, if the stackmap
+    /// was symbolized.
+    pub fn function_name(&self, function: &StkSizeRecord) -> Option<&str> {
+        self.symbols
+            .get(function.function_address)
+            .map(|symbol| symbol.name.as_str())
+    }
+
+    /// The demangled name of the symbol at the address of `function`.
+    #[cfg(feature = "demangle")]
+    pub fn demangled_function_name(&self, function: &StkSizeRecord) -> Option<String> {
+        self.function_name(function).map(demangle)
+    }
+
+    /// Find the function named `name` and get it together with its records.
+    /// `name` is compared against the symbol name and, if the `demangle` feature
+    /// is enabled, against the demangled symbol name.
+    pub fn function_by_name(&self, name: &str) -> Option<(&StkSizeRecord, &[StkMapRecord])> {
+        self.function_records().find(|(function, _)| {
+            let raw = self.function_name(function);
+            #[cfg(feature = "demangle")]
+            if raw.map_or(false, |raw| demangle(raw) == name) {
+                return true;
+            }
+            raw == Some(name)
+        })
+    }
+}
@@ -0,0 +1,491 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "from-elf")]
+use {
+    crate::{ParsingError, Symbols},
+    goblin::elf::Elf,
+    std::fs,
+    std::path::Path,
+};
+
+use crate::{Arch, LocationType, StackMap};
+
+/// An inconsistency found while validating a stackmap. Indices refer to
+/// `StackMap::stk_size_records`, `StackMap::stk_map_records` and the `locations`
+/// or `live_outs` of the respective record.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ValidationIssue {
+    /// The location has the type `LocationType::Invalid`.
+    InvalidLocation {
+        record_idx: usize,
+        location_idx: usize,
+    },
+    /// A ConstIndex location refers to a constant that does not exist.
+    ConstIndexOutOfBounds {
+        record_idx: usize,
+        location_idx: usize,
+        index: i32,
+    },
+    /// `num_functions` does not match the number of StkSizeRecords.
+    NumFunctionsMismatch { num_functions: u32, actual: usize },
+    /// `num_constants` does not match the number of large constants.
+    NumConstantsMismatch { num_constants: u32, actual: usize },
+    /// `num_records` does not match the number of StkMapRecords.
+    NumRecordsMismatch { num_records: u32, actual: usize },
+    /// The sum of all `record_count` values does not match the number of StkMapRecords.
+    RecordCountMismatch {
+        record_count_sum: u64,
+        actual: usize,
+    },
+    /// `num_locations` of a record does not match the number of its locations.
+    NumLocationsMismatch {
+        record_idx: usize,
+        num_locations: u16,
+        actual: usize,
+    },
+    /// `num_live_outs` of a record does not match the number of its live outs.
+    NumLiveOutsMismatch {
+        record_idx: usize,
+        num_live_outs: u16,
+        actual: usize,
+    },
+    /// A location refers to a register that does not exist on the target architecture.
+    UnknownLocationRegister {
+        record_idx: usize,
+        location_idx: usize,
+        dwarf_regnum: u16,
+    },
+    /// A location refers to a register that can not hold its value or address,
+    /// i.e., the program counter, or a vector register as the base of a Direct or
+    /// Indirect location.
+    InvalidLocationRegister {
+        record_idx: usize,
+        location_idx: usize,
+        dwarf_regnum: u16,
+    },
+    /// A live out refers to a register that does not exist on the target architecture.
+    UnknownLiveOutRegister {
+        record_idx: usize,
+        live_out_idx: usize,
+        dwarf_regnum: u16,
+    },
+    /// The location has a `loc_size` of zero.
+    ZeroLocationSize {
+        record_idx: usize,
+        location_idx: usize,
+    },
+    /// A Register location is larger than the register it refers to, or a Direct
+    /// location is larger than the address computed from its base register. The
+    /// size of Indirect locations is the size of the value in memory, which is not
+    /// limited by the base register.
+    LocationSizeMismatch {
+        record_idx: usize,
+        location_idx: usize,
+        loc_size: u16,
+        register_size: u16,
+    },
+    /// A Constant location has a non-zero `dwarf_regnum`.
+    ConstantWithRegister {
+        record_idx: usize,
+        location_idx: usize,
+        dwarf_regnum: u16,
+    },
+    /// The `instruction_offset` of a record lies outside of the function it belongs to.
+    InstructionOffsetOutOfFunction {
+        record_idx: usize,
+        function_address: u64,
+        instruction_offset: u32,
+        function_size: u64,
+    },
+    /// Multiple StkSizeRecords describe the same function.
+    DuplicateFunctionAddress {
+        function_idx: usize,
+        function_address: u64,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::InvalidLocation {
+                record_idx,
+                location_idx,
+            } => write!(
+                f,
+                "record {} location #{}: invalid location type",
+                record_idx,
+                location_idx + 1
+            ),
+            ValidationIssue::ConstIndexOutOfBounds {
+                record_idx,
+                location_idx,
+                index,
+            } => write!(
+                f,
+                "record {} location #{}: constant index {} is out of range",
+                record_idx,
+                location_idx + 1,
+                index
+            ),
+            ValidationIssue::NumFunctionsMismatch {
+                num_functions,
+                actual,
+            } => write!(
+                f,
+                "num_functions is {}, but there are {} function records",
+                num_functions, actual
+            ),
+            ValidationIssue::NumConstantsMismatch {
+                num_constants,
+                actual,
+            } => write!(
+                f,
+                "num_constants is {}, but there are {} constants",
+                num_constants, actual
+            ),
+            ValidationIssue::NumRecordsMismatch {
+                num_records,
+                actual,
+            } => write!(
+                f,
+                "num_records is {}, but there are {} records",
+                num_records, actual
+            ),
+            ValidationIssue::RecordCountMismatch {
+                record_count_sum,
+                actual,
+            } => write!(
+                f,
+                "function records account for {} records, but there are {} records",
+                record_count_sum, actual
+            ),
+            ValidationIssue::NumLocationsMismatch {
+                record_idx,
+                num_locations,
+                actual,
+            } => write!(
+                f,
+                "record {}: num_locations is {}, but there are {} locations",
+                record_idx, num_locations, actual
+            ),
+            ValidationIssue::NumLiveOutsMismatch {
+                record_idx,
+                num_live_outs,
+                actual,
+            } => write!(
+                f,
+                "record {}: num_live_outs is {}, but there are {} live outs",
+                record_idx, num_live_outs, actual
+            ),
+            ValidationIssue::UnknownLocationRegister {
+                record_idx,
+                location_idx,
+                dwarf_regnum,
+            } => write!(
+                f,
+                "record {} location #{}: unknown register R#{}",
+                record_idx,
+                location_idx + 1,
+                dwarf_regnum
+            ),
+            ValidationIssue::InvalidLocationRegister {
+                record_idx,
+                location_idx,
+                dwarf_regnum,
+            } => write!(
+                f,
+                "record {} location #{}: register R#{} can not be used for locations",
+                record_idx,
+                location_idx + 1,
+                dwarf_regnum
+            ),
+            ValidationIssue::UnknownLiveOutRegister {
+                record_idx,
+                live_out_idx,
+                dwarf_regnum,
+            } => write!(
+                f,
+                "record {} live out #{}: unknown register R#{}",
+                record_idx,
+                live_out_idx + 1,
+                dwarf_regnum
+            ),
+            ValidationIssue::ZeroLocationSize {
+                record_idx,
+                location_idx,
+            } => write!(
+                f,
+                "record {} location #{}: size is zero",
+                record_idx,
+                location_idx + 1
+            ),
+            ValidationIssue::LocationSizeMismatch {
+                record_idx,
+                location_idx,
+                loc_size,
+                register_size,
+            } => write!(
+                f,
+                "record {} location #{}: size {} exceeds the register width of {}",
+                record_idx,
+                location_idx + 1,
+                loc_size,
+                register_size
+            ),
+            ValidationIssue::ConstantWithRegister {
+                record_idx,
+                location_idx,
+                dwarf_regnum,
+            } => write!(
+                f,
+                "record {} location #{}: constant refers to register R#{}",
+                record_idx,
+                location_idx + 1,
+                dwarf_regnum
+            ),
+            ValidationIssue::InstructionOffsetOutOfFunction {
+                record_idx,
+                function_address,
+                instruction_offset,
+                function_size,
+            } => write!(
+                f,
+                "record {}: instruction offset {} exceeds the size {} of function {:#x}",
+                record_idx, instruction_offset, function_size, function_address
+            ),
+            ValidationIssue::DuplicateFunctionAddress {
+                function_idx,
+                function_address,
+            } => write!(
+                f,
+                "function record {}: duplicated function address {:#x}",
+                function_idx, function_address
+            ),
+        }
+    }
+}
+
+/// Information about the binary a stackmap belongs to that is required by
+/// checks that can not be performed on the stackmap alone.
+#[derive(Debug, Default, Clone)]
+pub struct ValidationContext {
+    /// The architecture the stackmap was emitted for. If set, register numbers
+    /// and sizes are checked.
+    pub arch: Option<Arch>,
+    /// Size of each function keyed by its address. If a function is contained,
+    /// the `instruction_offset` of its records is checked.
+    pub function_sizes: HashMap<u64, u64>,
+}
+
+impl ValidationContext {
+    /// Create a context from the ELF header and the function symbols of `elf`
+    /// (see `Symbols::from_elf`).
+    #[cfg(feature = "from-elf")]
+    pub fn from_elf(elf: &Elf) -> ValidationContext {
+        let function_sizes = Symbols::from_elf(elf)
+            .iter()
+            .filter(|sym| sym.size != 0)
+            .map(|sym| (sym.address, sym.size))
+            .collect();
+
+        ValidationContext {
+            arch: Arch::from_elf_machine(elf.header.e_machine),
+            function_sizes,
+        }
+    }
+
+    /// Create a context for the binary `path` points to (see `from_elf`).
+    #[cfg(feature = "from-elf")]
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Result<ValidationContext, ParsingError> {
+        let bytes = fs::read(path)?;
+        let elf = Elf::parse(&bytes)?;
+        Ok(ValidationContext::from_elf(&elf))
+    }
+}
+
+impl StackMap {
+    /// Check the stackmap for inconsistencies. Since stackmaps might be parsed from
+    /// binaries we do not trust, the returned issues should be checked before using
+    /// the content of the stackmap. An empty result means that no issue was found.
+    ///
+    /// Checks that require knowledge about the binary are skipped, use
+    /// `validate_with` to perform them.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.validate_with(&ValidationContext::default())
+    }
+
+    /// Same as `validate`, but additionally perform all checks that are possible
+    /// with the information provided by `ctx`.
+    pub fn validate_with(&self, ctx: &ValidationContext) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        if self.num_functions as usize != self.stk_size_records.len() {
+            issues.push(ValidationIssue::NumFunctionsMismatch {
+                num_functions: self.num_functions,
+                actual: self.stk_size_records.len(),
+            });
+        }
+        if self.num_constants as usize != self.large_constants.len() {
+            issues.push(ValidationIssue::NumConstantsMismatch {
+                num_constants: self.num_constants,
+                actual: self.large_constants.len(),
+            });
+        }
+        if self.num_records as usize != self.stk_map_records.len() {
+            issues.push(ValidationIssue::NumRecordsMismatch {
+                num_records: self.num_records,
+                actual: self.stk_map_records.len(),
+            });
+        }
+
+        let record_count_sum = self
+            .stk_size_records
+            .iter()
+            .fold(0u64, |acc, f| acc.saturating_add(f.record_count));
+        if record_count_sum != self.stk_map_records.len() as u64 {
+            issues.push(ValidationIssue::RecordCountMismatch {
+                record_count_sum,
+                actual: self.stk_map_records.len(),
+            });
+        }
+
+        let mut seen_addresses = HashSet::new();
+        for (function_idx, function) in self.stk_size_records.iter().enumerate() {
+            if !seen_addresses.insert(function.function_address) {
+                issues.push(ValidationIssue::DuplicateFunctionAddress {
+                    function_idx,
+                    function_address: function.function_address,
+                });
+            }
+        }
+
+        // Check the records against the size of the function they belong to.
+        let mut record_idx = 0usize;
+        for (function, records) in self.function_records() {
+            if let Some(function_size) = ctx.function_sizes.get(&function.function_address) {
+                for (idx, record) in records.iter().enumerate() {
+                    if record.instruction_offset as u64 >= *function_size {
+                        issues.push(ValidationIssue::InstructionOffsetOutOfFunction {
+                            record_idx: record_idx + idx,
+                            function_address: function.function_address,
+                            instruction_offset: record.instruction_offset,
+                            function_size: *function_size,
+                        });
+                    }
+                }
+            }
+            record_idx += records.len();
+        }
+
+        for (record_idx, record) in self.stk_map_records.iter().enumerate() {
+            if record.num_locations as usize != record.locations.len() {
+                issues.push(ValidationIssue::NumLocationsMismatch {
+                    record_idx,
+                    num_locations: record.num_locations,
+                    actual: record.locations.len(),
+                });
+            }
+            if record.num_live_outs as usize != record.live_outs.len() {
+                issues.push(ValidationIssue::NumLiveOutsMismatch {
+                    record_idx,
+                    num_live_outs: record.num_live_outs,
+                    actual: record.live_outs.len(),
+                });
+            }
+
+            for (location_idx, loc) in record.locations.iter().enumerate() {
+                if loc.loc_size == 0 {
+                    issues.push(ValidationIssue::ZeroLocationSize {
+                        record_idx,
+                        location_idx,
+                    });
+                }
+
+                match loc.loc_type {
+                    LocationType::Invalid => issues.push(ValidationIssue::InvalidLocation {
+                        record_idx,
+                        location_idx,
+                    }),
+                    LocationType::ConstIndex => {
+                        let in_bounds = usize::try_from(loc.offset_or_constant)
+                            .map_or(false, |idx| idx < self.large_constants.len());
+                        if !in_bounds {
+                            issues.push(ValidationIssue::ConstIndexOutOfBounds {
+                                record_idx,
+                                location_idx,
+                                index: loc.offset_or_constant,
+                            });
+                        }
+                    }
+                    LocationType::Constant => {
+                        if loc.dwarf_regnum != 0 {
+                            issues.push(ValidationIssue::ConstantWithRegister {
+                                record_idx,
+                                location_idx,
+                                dwarf_regnum: loc.dwarf_regnum,
+                            });
+                        }
+                    }
+                    LocationType::Register | LocationType::Direct | LocationType::Indirect => {
+                        let arch = match ctx.arch {
+                            Some(arch) => arch,
+                            None => continue,
+                        };
+                        let register_size = match arch.register_size(loc.dwarf_regnum) {
+                            Some(register_size) => register_size,
+                            None => {
+                                issues.push(ValidationIssue::UnknownLocationRegister {
+                                    record_idx,
+                                    location_idx,
+                                    dwarf_regnum: loc.dwarf_regnum,
+                                });
+                                continue;
+                            }
+                        };
+                        let is_base = loc.loc_type != LocationType::Register;
+                        if arch.program_counter() == Some(loc.dwarf_regnum)
+                            || (is_base && arch.is_vector_register(loc.dwarf_regnum))
+                        {
+                            issues.push(ValidationIssue::InvalidLocationRegister {
+                                record_idx,
+                                location_idx,
+                                dwarf_regnum: loc.dwarf_regnum,
+                            });
+                        } else if loc.loc_type != LocationType::Indirect
+                            && loc.loc_size > register_size
+                        {
+                            issues.push(ValidationIssue::LocationSizeMismatch {
+                                record_idx,
+                                location_idx,
+                                loc_size: loc.loc_size,
+                                register_size,
+                            });
+                        }
+                    }
+                }
+            }
+
+            if let Some(arch) = ctx.arch {
+                for (live_out_idx, live_out) in record.live_outs.iter().enumerate() {
+                    if arch.register_name(live_out.dwarf_regnum).is_none() {
+                        issues.push(ValidationIssue::UnknownLiveOutRegister {
+                            record_idx,
+                            live_out_idx,
+                            dwarf_regnum: live_out.dwarf_regnum,
+                        });
+                    }
+                }
+            }
+        }
+
+        issues
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use crate::{Arch, RegisterProvider};
+
+/// Offset of xmm0 within the FXSAVE area (`struct user_fpregs_struct`).
+const FXSAVE_XMM_OFFSET: usize = 160;
+const FXSAVE_SIZE: usize = 512;
+/// Offset of the XSTATE_BV field within the XSAVE header.
+const XSAVE_XSTATE_BV_OFFSET: usize = 512;
+/// Offset of the upper halves of ymm0-ymm15 in the standard XSAVE format.
+const XSAVE_YMM_HI_OFFSET: usize = 576;
+const XSTATE_SSE: u64 = 1 << 1;
+const XSTATE_AVX: u64 = 1 << 2;
+/// Size of the vector registers of `struct user_fpsimd_state` on AArch64.
+const FPSIMD_VREGS_SIZE: usize = 32 * 16;
+
+/// The values of the vector registers of a stopped thread, indexed by their DWARF
+/// register number. Values are stored in little endian byte order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VectorRegisters {
+    values: BTreeMap<u16, Vec<u8>>,
+}
+
+impl VectorRegisters {
+    pub fn new() -> VectorRegisters {
+        VectorRegisters::default()
+    }
+
+    /// Parse the xmm registers from an x86_64 FXSAVE area, as returned by
+    /// `PTRACE_GETFPREGS`. Returns None if `area` is too small.
+    pub fn from_fxsave(area: &[u8]) -> Option<VectorRegisters> {
+        if area.len() < FXSAVE_SIZE {
+            return None;
+        }
+        let mut regs = VectorRegisters::new();
+        for idx in 0..16 {
+            let offset = FXSAVE_XMM_OFFSET + idx * 16;
+            regs.set(
+                Arch::X86_64.vector_register(idx)?,
+                &area[offset..offset + 16],
+            );
+        }
+        Some(regs)
+    }
+
+    /// Parse the xmm and ymm registers from an x86_64 XSAVE area in standard
+    /// (non-compacted) format, as returned by `PTRACE_GETREGSET` with
+    /// `NT_X86_XSTATE`. If the area does not contain the AVX state, only the
+    /// lower 16 bytes of each register are available. Returns None if `area` is
+    /// too small.
+    pub fn from_xsave(area: &[u8]) -> Option<VectorRegisters> {
+        let xstate_bv = area.get(XSAVE_XSTATE_BV_OFFSET..XSAVE_XSTATE_BV_OFFSET + 8)?;
+        let xstate_bv = u64::from_le_bytes(xstate_bv.try_into().ok()?);
+        let mut regs = VectorRegisters::from_fxsave(area)?;
+        let has_avx = area.len() >= XSAVE_YMM_HI_OFFSET + 16 * 16;
+        for idx in 0..16 {
+            let regnum = Arch::X86_64.vector_register(idx)?;
+            let mut value = regs.values.remove(&regnum)?;
+            // Components not set in XSTATE_BV are in their initial state (zero).
+            if xstate_bv & XSTATE_SSE == 0 {
+                value.fill(0);
+            }
+            if has_avx {
+                let offset = XSAVE_YMM_HI_OFFSET + idx * 16;
+                match xstate_bv & XSTATE_AVX {
+                    0 => value.extend_from_slice(&[0; 16]),
+                    _ => value.extend_from_slice(&area[offset..offset + 16]),
+                }
+            }
+            regs.set(regnum, &value);
+        }
+        Some(regs)
+    }
+
+    /// Parse the v registers from an AArch64 `struct user_fpsimd_state`, as
+    /// returned by `PTRACE_GETREGSET` with `NT_PRFPREG`. Returns None if `area` is
+    /// too small.
+    pub fn from_aarch64_fpsimd(area: &[u8]) -> Option<VectorRegisters> {
+        if area.len() < FPSIMD_VREGS_SIZE {
+            return None;
+        }
+        let mut regs = VectorRegisters::new();
+        for idx in 0..32 {
+            let offset = idx * 16;
+            regs.set(
+                Arch::AArch64.vector_register(idx)?,
+                &area[offset..offset + 16],
+            );
+        }
+        Some(regs)
+    }
+
+    /// Get the value of the register `dwarf_regnum` in little endian byte order.
+    pub fn get(&self, dwarf_regnum: u16) -> Option<&[u8]> {
+        self.values.get(&dwarf_regnum).map(Vec::as_slice)
+    }
+
+    /// Set the value of the register `dwarf_regnum` to `value`, which is given in
+    /// little endian byte order.
+    pub fn set(&mut self, dwarf_regnum: u16, value: &[u8]) {
+        self.values.insert(dwarf_regnum, value.to_vec());
+    }
+
+    /// Combine these vector registers with the general purpose registers
+    /// provided by `general` into a single `RegisterProvider`.
+    pub fn with_general<R: RegisterProvider>(&self, general: R) -> WithVectorRegisters<'_, R> {
+        WithVectorRegisters {
+            general,
+            vector: self,
+        }
+    }
+}
+
+impl RegisterProvider for VectorRegisters {
+    fn read_register(&self, dwarf_regnum: u16) -> Option<u64> {
+        let mut buf = [0u8; 8];
+        self.read_register_bytes(dwarf_regnum, &mut buf)
+            .then(|| u64::from_le_bytes(buf))
+    }
+
+    fn read_register_bytes(&self, dwarf_regnum: u16, buf: &mut [u8]) -> bool {
+        match self
+            .get(dwarf_regnum)
+            .and_then(|value| value.get(..buf.len()))
+        {
+            Some(value) => {
+                buf.copy_from_slice(value);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A `RegisterProvider` for both the general purpose and the vector registers of
+/// a thread (see `VectorRegisters::with_general`).
+#[derive(Debug, Clone)]
+pub struct WithVectorRegisters<'v, R> {
+    pub general: R,
+    pub vector: &'v VectorRegisters,
+}
+
+impl<R: RegisterProvider> RegisterProvider for WithVectorRegisters<'_, R> {
+    fn read_register(&self, dwarf_regnum: u16) -> Option<u64> {
+        self.vector
+            .read_register(dwarf_regnum)
+            .or_else(|| self.general.read_register(dwarf_regnum))
+    }
+
+    fn read_register_bytes(&self, dwarf_regnum: u16, buf: &mut [u8]) -> bool {
+        if self.vector.get(dwarf_regnum).is_some() {
+            return self.vector.read_register_bytes(dwarf_regnum, buf);
+        }
+        self.general.read_register_bytes(dwarf_regnum, buf)
+    }
+}
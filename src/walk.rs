@@ -0,0 +1,270 @@
+use std::{collections::BTreeMap, fmt};
+
+#[cfg(feature = "dwarf")]
+use crate::CfiUnwinder;
+use crate::{Arch, IndexedRecord, MemoryProvider, RegisterProvider, StackMapIndex, StkSizeRecord};
+
+/// The `stack_size` LLVM records for functions with a dynamically sized frame,
+/// e.g., due to variable sized allocas.
+pub const DYNAMIC_STACK_SIZE: u64 = u64::MAX;
+
+impl StkSizeRecord {
+    /// Whether the frame of this function has a fixed size, i.e., `stack_size`
+    /// can be used to find the caller's frame.
+    pub fn has_fixed_stack_size(&self) -> bool {
+        self.stack_size != DYNAMIC_STACK_SIZE
+    }
+}
+
+/// The known register values of a frame, indexed by their DWARF register number,
+/// together with its program counter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Registers {
+    pub pc: u64,
+    values: BTreeMap<u16, u64>,
+}
+
+impl Registers {
+    /// Create a register set without any known register values.
+    pub fn new(pc: u64) -> Registers {
+        Registers {
+            pc,
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, dwarf_regnum: u16) -> Option<u64> {
+        self.values.get(&dwarf_regnum).copied()
+    }
+
+    pub fn set(&mut self, dwarf_regnum: u16, value: u64) {
+        self.values.insert(dwarf_regnum, value);
+    }
+
+    /// Mark the value of the register `dwarf_regnum` as unknown.
+    pub fn remove(&mut self, dwarf_regnum: u16) -> Option<u64> {
+        self.values.remove(&dwarf_regnum)
+    }
+
+    /// Iterate over all known registers as (DWARF register number, value) pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u64)> + '_ {
+        self.values.iter().map(|(regnum, value)| (*regnum, *value))
+    }
+}
+
+impl RegisterProvider for Registers {
+    fn read_register(&self, dwarf_regnum: u16) -> Option<u64> {
+        self.get(dwarf_regnum)
+    }
+}
+
+/// A stack frame of a function that is covered by the stackmaps.
+#[derive(Debug, Clone)]
+pub struct Frame<'a> {
+    /// The instruction the frame is currently executing. Except for the first
+    /// frame, this is the return address of the callee.
+    pub pc: u64,
+    /// The stack pointer of the frame at `pc`.
+    pub sp: u64,
+    pub function: &'a StkSizeRecord,
+    /// The record located at `pc`, if any.
+    pub record: Option<IndexedRecord<'a>>,
+    /// The registers whose values in this frame are known. Besides the stack
+    /// pointer, these are only available for outer frames if they were unwound
+    /// using CFI.
+    pub registers: Registers,
+}
+
+/// The reason why a `FrameWalker` stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalkStop {
+    /// The address is not covered by any function of the stackmaps.
+    NotCovered(u64),
+    /// The function located at the given address has a dynamically sized frame
+    /// and no CFI is available for it.
+    DynamicFrame(u64),
+    /// The return address could not be read from the given address.
+    MemoryUnreadable(u64),
+    /// Walking frames without CFI is not supported for the architecture.
+    UnsupportedArch(Arch),
+    /// The CFI of the frame at the given address marks it as the outermost frame.
+    EndOfStack(u64),
+    /// The CFI of the frame refers to the register with the given DWARF register
+    /// number, whose value is not known.
+    RegisterUnavailable(u16),
+    /// The CFI for the given address is malformed or uses unsupported rules
+    /// (e.g., DWARF expressions).
+    UnsupportedCfi(u64),
+}
+
+impl fmt::Display for WalkStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalkStop::NotCovered(pc) => write!(f, "{:#x} is not covered by the stackmaps", pc),
+            WalkStop::DynamicFrame(pc) => {
+                write!(f, "the function at {:#x} has a dynamically sized frame", pc)
+            }
+            WalkStop::MemoryUnreadable(address) => {
+                write!(f, "failed to read return address at {:#x}", address)
+            }
+            WalkStop::UnsupportedArch(arch) => {
+                write!(f, "walking frames is not supported for {:?}", arch)
+            }
+            WalkStop::EndOfStack(pc) => write!(f, "the frame at {:#x} is the outermost", pc),
+            WalkStop::RegisterUnavailable(regnum) => {
+                write!(
+                    f,
+                    "register R#{} required for unwinding is not available",
+                    regnum
+                )
+            }
+            WalkStop::UnsupportedCfi(pc) => {
+                write!(f, "the CFI for {:#x} is malformed or not supported", pc)
+            }
+        }
+    }
+}
+
+/// Walks the stack of a stopped thread using the `stack_size` of the functions
+/// of the stackmaps, which also works for functions compiled without frame
+/// pointers. Each function is expected to store its return address directly
+/// above its fixed size frame, as done on x86_64. If CFI is provided (see
+/// `with_cfi`), it takes precedence and is used to recover the callee-saved
+/// registers of the outer frames as well.
+///
+/// The walker yields one `Frame` for each frame and stops at the first frame
+/// that is not covered by the stackmaps or whose caller can not be determined
+/// (see `stop_reason`).
+pub struct FrameWalker<'a, 'm, M> {
+    index: &'m StackMapIndex<'a>,
+    arch: Arch,
+    mem: &'m M,
+    #[cfg(feature = "dwarf")]
+    cfi: Option<&'m CfiUnwinder>,
+    /// The registers of the next frame to yield, or None if the walk stopped.
+    next: Option<Registers>,
+    is_first: bool,
+    stop: Option<WalkStop>,
+}
+
+impl<'a, 'm, M: MemoryProvider> FrameWalker<'a, 'm, M> {
+    /// Start walking at the frame with the program counter `pc` and stack pointer
+    /// `sp`. `pc` must not be located within the prologue or epilogue of its
+    /// function, e.g., a patch point or call site is fine.
+    pub fn new(
+        index: &'m StackMapIndex<'a>,
+        arch: Arch,
+        mem: &'m M,
+        pc: u64,
+        sp: u64,
+    ) -> FrameWalker<'a, 'm, M> {
+        let mut registers = Registers::new(pc);
+        registers.set(arch.stack_pointer(), sp);
+        FrameWalker::from_registers(index, arch, mem, registers)
+    }
+
+    /// Start walking at the frame described by `registers`, which must contain
+    /// at least the stack pointer.
+    pub fn from_registers(
+        index: &'m StackMapIndex<'a>,
+        arch: Arch,
+        mem: &'m M,
+        registers: Registers,
+    ) -> FrameWalker<'a, 'm, M> {
+        FrameWalker {
+            index,
+            arch,
+            mem,
+            #[cfg(feature = "dwarf")]
+            cfi: None,
+            next: Some(registers),
+            is_first: true,
+            stop: None,
+        }
+    }
+
+    /// Use the CFI of `cfi` to unwind frames. Frames without CFI are still
+    /// unwound using their `stack_size`.
+    #[cfg(feature = "dwarf")]
+    pub fn with_cfi(mut self, cfi: &'m CfiUnwinder) -> FrameWalker<'a, 'm, M> {
+        self.cfi = Some(cfi);
+        self
+    }
+
+    /// Why the walk stopped, or None if it has not stopped yet.
+    pub fn stop_reason(&self) -> Option<&WalkStop> {
+        self.stop.as_ref()
+    }
+
+    /// Get the registers of the caller of `frame`.
+    fn caller(&self, frame: &Frame<'a>) -> Result<Registers, WalkStop> {
+        #[cfg(feature = "dwarf")]
+        if let Some(cfi) = self.cfi {
+            if let Some(caller) =
+                cfi.unwind(self.arch, &frame.registers, self.is_first, self.mem)?
+            {
+                return Ok(caller);
+            }
+        }
+
+        if self.arch != Arch::X86_64 {
+            return Err(WalkStop::UnsupportedArch(self.arch));
+        }
+        if !frame.function.has_fixed_stack_size() {
+            return Err(WalkStop::DynamicFrame(frame.pc));
+        }
+
+        let return_address_slot = frame.sp.wrapping_add(frame.function.stack_size);
+        let mut buf = [0u8; 8];
+        if !self.mem.read_memory(return_address_slot, &mut buf) {
+            return Err(WalkStop::MemoryUnreadable(return_address_slot));
+        }
+        // Without CFI, the values of all other registers are unknown.
+        let mut caller = Registers::new(u64::from_le_bytes(buf));
+        caller.set(
+            self.arch.stack_pointer(),
+            return_address_slot.wrapping_add(8),
+        );
+        Ok(caller)
+    }
+}
+
+impl<'a, M: MemoryProvider> Iterator for FrameWalker<'a, '_, M> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let registers = self.next.take()?;
+        let pc = registers.pc;
+        let sp = match registers.get(self.arch.stack_pointer()) {
+            Some(sp) => sp,
+            None => {
+                self.stop = Some(WalkStop::RegisterUnavailable(self.arch.stack_pointer()));
+                return None;
+            }
+        };
+        // The end of the last function may be unknown, but the return address of
+        // a call with a record is known to belong to the function of the record.
+        let record = self.index.record_at(pc);
+        let function = match self.index.function_at(pc).or(record.map(|r| r.function)) {
+            Some(function) => function,
+            None => {
+                self.stop = Some(WalkStop::NotCovered(pc));
+                return None;
+            }
+        };
+
+        let frame = Frame {
+            pc,
+            sp,
+            function,
+            record,
+            registers,
+        };
+        match self.caller(&frame) {
+            Ok(caller) => self.next = Some(caller),
+            Err(stop) => self.stop = Some(stop),
+        }
+        self.is_first = false;
+        Some(frame)
+    }
+}
@@ -0,0 +1,71 @@
+#![cfg(all(feature = "from-elf", feature = "from-macho"))]
+
+use std::fs;
+
+use llvm_stackmap::{ParseOptions, ParsingError, StackMap};
+
+mod common;
+use common::fixture;
+
+/// The (name, address) of the functions of a stackmap.
+type Functions<'a> = Vec<(Option<&'a str>, u64)>;
+
+/// Get the member names and the (name, address) of all functions of `members`.
+fn functions(members: &[(String, Vec<StackMap>)]) -> Vec<(&str, Functions<'_>)> {
+    members
+        .iter()
+        .map(|(member, maps)| {
+            assert_eq!(maps.len(), 1, "{}", member);
+            let functions = maps[0]
+                .functions()
+                .iter()
+                .map(|f| (maps[0].function_name(f), f.function_address))
+                .collect();
+            (member.as_str(), functions)
+        })
+        .collect()
+}
+
+fn expected() -> Vec<(&'static str, Functions<'static>)> {
+    vec![
+        ("stackmaps.o", vec![(Some("foo"), 0x0), (Some("bar"), 0x40)]),
+        (
+            "stackmaps-x86_64.o",
+            vec![(Some("foo"), 0x0), (Some("bar"), 0x40)],
+        ),
+    ]
+}
+
+#[test]
+fn members_are_parsed_and_relocated() {
+    for name in ["stackmaps.a", "stackmaps-bsd.a"] {
+        let members = StackMap::from_archive_path(fixture(name), &ParseOptions::default()).unwrap();
+        assert_eq!(functions(&members), expected(), "{}", name);
+
+        let bytes = fs::read(fixture(name)).unwrap();
+        let from_bytes = StackMap::from_archive_bytes(&bytes, &ParseOptions::default()).unwrap();
+        assert_eq!(functions(&from_bytes), expected(), "{}", name);
+    }
+}
+
+#[test]
+fn thin_archive_members_are_read_relative_to_the_archive() {
+    let members =
+        StackMap::from_archive_path(fixture("stackmaps-thin.a"), &ParseOptions::default()).unwrap();
+    assert_eq!(functions(&members), expected());
+
+    let bytes = fs::read(fixture("stackmaps-thin.a")).unwrap();
+    match StackMap::from_archive_bytes(&bytes, &ParseOptions::default()) {
+        Err(ParsingError::Malformed(_)) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn truncated_archive_is_rejected() {
+    let bytes = fs::read(fixture("stackmaps.a")).unwrap();
+    match StackMap::from_archive_bytes(&bytes[..bytes.len() - 100], &ParseOptions::default()) {
+        Err(ParsingError::Malformed(msg)) => assert!(msg.contains("exceeds the archive")),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
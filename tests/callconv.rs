@@ -0,0 +1,73 @@
+use llvm_stackmap::{Arch, CallingConvention, LiveOut, StkMapRecord};
+
+/// A record with live-outs of the given (DWARF register number, size) pairs.
+fn record(live_outs: &[(u16, u8)]) -> StkMapRecord {
+    let live_outs = live_outs
+        .iter()
+        .map(|(regnum, size)| LiveOut::new(*regnum, *size))
+        .collect();
+    StkMapRecord::new(1, 0, vec![], live_outs).unwrap()
+}
+
+fn to_save(record: &StkMapRecord, arch: Arch, cc: CallingConvention) -> Option<Vec<u16>> {
+    record
+        .live_outs_to_save(arch, cc)
+        .map(|live_outs| live_outs.iter().map(|l| l.dwarf_regnum()).collect())
+}
+
+#[test]
+fn x86_64_live_outs_to_save() {
+    // rax, rbx, rsp, r11, r12, xmm0, ymm15
+    let record = record(&[(0, 8), (3, 8), (7, 8), (11, 8), (12, 8), (17, 16), (32, 32)]);
+    let arch = Arch::X86_64;
+    assert_eq!(
+        to_save(&record, arch, CallingConvention::SysV),
+        Some(vec![0, 11, 17, 32])
+    );
+    assert_eq!(
+        to_save(&record, arch, CallingConvention::PreserveAll),
+        Some(vec![11])
+    );
+    assert_eq!(
+        to_save(&record, arch, CallingConvention::AnyReg),
+        Some(vec![])
+    );
+    assert_eq!(to_save(&record, arch, CallingConvention::Aapcs64), None);
+}
+
+#[test]
+fn aarch64_live_outs_to_save() {
+    // x0, x18, x19, lr, sp, v0, v8 (lower and full), v15 (lower), v16
+    let record = record(&[
+        (0, 8),
+        (18, 8),
+        (19, 8),
+        (30, 8),
+        (31, 8),
+        (64, 16),
+        (72, 8),
+        (72, 16),
+        (79, 8),
+        (80, 8),
+    ]);
+    let arch = Arch::AArch64;
+    // Only the lower halves of v8-v15 are callee-saved.
+    assert_eq!(
+        to_save(&record, arch, CallingConvention::Aapcs64),
+        Some(vec![0, 18, 30, 64, 72, 80])
+    );
+    assert_eq!(
+        to_save(&record, arch, CallingConvention::PreserveAll),
+        Some(vec![0, 18, 30, 64])
+    );
+    assert_eq!(
+        to_save(&record, arch, CallingConvention::AnyReg),
+        Some(vec![30])
+    );
+    assert_eq!(to_save(&record, arch, CallingConvention::SysV), None);
+
+    let saved = record
+        .live_outs_to_save(arch, CallingConvention::Aapcs64)
+        .unwrap();
+    assert_eq!(saved[4], LiveOut::new(72, 16));
+}
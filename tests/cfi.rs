@@ -0,0 +1,83 @@
+#![cfg(feature = "dwarf")]
+
+use std::collections::HashMap;
+
+use llvm_stackmap::{Arch, CfiUnwinder, Registers, WalkStop};
+
+mod common;
+use common::fixture;
+
+/// Memory that holds the 8 byte values of `values`.
+fn memory(values: &[(u64, u64)]) -> impl Fn(u64, &mut [u8]) -> bool {
+    let values = values.iter().copied().collect::<HashMap<_, _>>();
+    move |address, buf: &mut [u8]| match values.get(&address) {
+        Some(value) => {
+            buf.copy_from_slice(&value.to_le_bytes());
+            true
+        }
+        None => false,
+    }
+}
+
+/// The registers of `foo` of `stackmaps` after its prologue, which set up rbp as
+/// frame pointer and saved rbx and r14.
+fn foo_registers(pc: u64) -> Registers {
+    let mut regs = Registers::new(pc);
+    regs.set(6, 0x8000);
+    regs.set(7, 0x7f00);
+    regs.set(12, 0x33);
+    regs
+}
+
+#[test]
+fn x86_64_frame_is_unwound() {
+    let unwinder = CfiUnwinder::from_path(fixture("stackmaps"))
+        .unwrap()
+        .with_load_bias(0x10000);
+    let mem = memory(&[
+        (0x7ff0, 0x11),
+        (0x7ff8, 0x22),
+        (0x8000, 0x9000),
+        (0x8008, 0x1234),
+    ]);
+    let caller = unwinder
+        .unwind(Arch::X86_64, &foo_registers(0x101c0), true, &mem)
+        .unwrap()
+        .unwrap();
+
+    let mut expected = Registers::new(0x1234);
+    // rbx, rbp, rsp, r12 (unchanged) and r14
+    for (regnum, value) in [(3, 0x11), (6, 0x9000), (7, 0x8010), (12, 0x33), (14, 0x22)] {
+        expected.set(regnum, value);
+    }
+    assert_eq!(caller, expected);
+}
+
+#[test]
+fn x86_64_unwinding_fails_on_unreadable_memory() {
+    let unwinder = CfiUnwinder::from_path(fixture("stackmaps")).unwrap();
+    let mem = memory(&[]);
+    match unwinder.unwind(Arch::X86_64, &foo_registers(0x1c0), true, &mem) {
+        Err(WalkStop::MemoryUnreadable(address)) => assert!((0x7ff0..0x8010).contains(&address)),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    // Outside of the functions with CFI
+    assert_eq!(
+        unwinder.unwind(Arch::X86_64, &foo_registers(0x100), true, &mem),
+        Ok(None)
+    );
+}
+
+#[test]
+fn aarch64_leaf_returns_via_link_register() {
+    let unwinder = CfiUnwinder::from_path(fixture("cfi-aarch64.o")).unwrap();
+    let mut regs = Registers::new(0x4);
+    regs.set(31, 0x7f00);
+    regs.set(30, 0x1234);
+    let caller = unwinder
+        .unwind(Arch::AArch64, &regs, true, &memory(&[]))
+        .unwrap()
+        .unwrap();
+    assert_eq!(caller.pc, 0x1234);
+    assert_eq!(caller.get(31), Some(0x7f00));
+}
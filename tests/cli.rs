@@ -0,0 +1,65 @@
+#![cfg(feature = "cli")]
+
+use std::{fs, process::Command};
+
+use llvm_stackmap::StackMap;
+
+mod common;
+use common::fixture;
+
+/// Run `llvm-stackmap dump` with `args` and get its output.
+fn dump(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_llvm-stackmap"))
+        .arg("dump")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn readobj_format_matches_llvm_readobj() {
+    let path = fixture("stackmaps");
+    let output = dump(&[path.to_str().unwrap()]);
+    let expected = fs::read_to_string(fixture("stackmaps.readobj.txt")).unwrap();
+    let start = expected.find("LLVM StackMap Version").unwrap();
+    assert_eq!(output, expected[start..]);
+}
+
+#[test]
+fn json_format_contains_the_stackmaps() {
+    let path = fixture("stackmaps");
+    let output = dump(&["--format", "json", path.to_str().unwrap()]);
+    let maps: Vec<StackMap> = serde_json::from_str(&output).unwrap();
+    let expected = StackMap::from_path(&path).unwrap();
+    assert_eq!(maps.len(), 1);
+    assert_eq!(maps[0].to_string(), expected[0].to_string());
+}
+
+#[test]
+fn csv_format_has_one_row_per_location() {
+    let path = fixture("stackmaps");
+    let output = dump(&["--format", "csv", "--id", "3", path.to_str().unwrap()]);
+    assert_eq!(
+        output,
+        "map,function_address,function_name,stack_size,patch_point_id,instruction_offset,\
+         location,type,size,dwarf_regnum,offset_or_constant,num_live_outs\n\
+         0,0x1e0,bar,8,3,7,1,Register,4,0,0,0\n\
+         0,0x1e0,bar,8,3,7,2,ConstIndex,8,0,1,0\n"
+    );
+}
+
+#[test]
+fn csv_fields_are_quoted() {
+    let path = fixture("names.o");
+    let output = dump(&["--format", "csv", path.to_str().unwrap()]);
+    let rows = output.lines().skip(1).collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        [
+            "0,0x0,\"foo(int, int)\",8,1,7,1,Register,4,0,0,0",
+            "0,0x10,\"say \"\"hi\"\"\",8,2,6,1,Register,4,0,0,0",
+        ]
+    );
+}
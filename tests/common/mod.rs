@@ -0,0 +1,11 @@
+// Not every test uses every helper, depending on the enabled features.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+/// Get the path of the test fixture `name` (see `tests/fixtures/README.md`).
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
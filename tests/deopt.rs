@@ -0,0 +1,227 @@
+use llvm_stackmap::{
+    DeoptError, DeoptSchema, DeoptSlot, EvaluationError, Location, LocationType, RegisterProvider,
+    StackMap, StackMapIndex, StkMapRecord, StkSizeRecord, Value,
+};
+
+/// The DWARF register numbers of rbx, rsp and xmm1.
+const RBX: u16 = 3;
+const RSP: u16 = 7;
+const XMM1: u16 = 18;
+
+const RSP_VALUE: u64 = 0x8000;
+
+fn location(loc_type: LocationType, loc_size: u16, dwarf_regnum: u16, offset: i32) -> Location {
+    Location {
+        loc_type,
+        loc_size,
+        dwarf_regnum,
+        offset_or_constant: offset,
+        ..Default::default()
+    }
+}
+
+/// A map with a single record at 0x1010 with the given locations.
+fn map_with(mut locations: Vec<Location>) -> Vec<StackMap> {
+    let mut map = StackMap::default();
+    let idx = map.push_constant(0x1234_5678_9abc).unwrap();
+    locations.push(location(LocationType::ConstIndex, 8, 0, idx));
+    let record = StkMapRecord::new(1, 0x10, locations, vec![]).unwrap();
+    let function = StkSizeRecord {
+        function_address: 0x1000,
+        stack_size: 32,
+        ..Default::default()
+    };
+    map.push_function(function, vec![record]).unwrap();
+    vec![map]
+}
+
+/// The locations of a typical frame: a 4 byte register, a spilled 2 byte value,
+/// an alloca, a 16 byte vector and a small constant. `map_with` appends a large
+/// constant as location #6.
+fn locations() -> Vec<Location> {
+    vec![
+        location(LocationType::Register, 4, RBX, 0),
+        location(LocationType::Indirect, 2, RSP, 8),
+        location(LocationType::Direct, 8, RSP, 16),
+        location(LocationType::Register, 16, XMM1, 0),
+        location(LocationType::Constant, 8, 0, -1),
+    ]
+}
+
+struct Registers;
+
+impl RegisterProvider for Registers {
+    fn read_register(&self, dwarf_regnum: u16) -> Option<u64> {
+        match dwarf_regnum {
+            RBX => Some(0xdead_beef_0000_002a),
+            RSP => Some(RSP_VALUE),
+            _ => None,
+        }
+    }
+
+    fn read_register_bytes(&self, dwarf_regnum: u16, buf: &mut [u8]) -> bool {
+        if dwarf_regnum != XMM1 {
+            return match self.read_register(dwarf_regnum) {
+                Some(value) if buf.len() <= 8 => {
+                    buf.copy_from_slice(&value.to_le_bytes()[..buf.len()]);
+                    true
+                }
+                _ => false,
+            };
+        }
+        let xmm1 = 0x0011_2233_4455_6677_8899_aabb_ccdd_eeffu128.to_le_bytes();
+        match xmm1.get(..buf.len()) {
+            Some(bytes) => {
+                buf.copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The stack contains 0x0102 at rsp+8.
+fn memory(address: u64, buf: &mut [u8]) -> bool {
+    let stack = [0u8, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x01, 0, 0, 0, 0, 0, 0];
+    match address
+        .checked_sub(RSP_VALUE)
+        .and_then(|offset| stack.get(offset as usize..offset as usize + buf.len()))
+    {
+        Some(bytes) => {
+            buf.copy_from_slice(bytes);
+            true
+        }
+        None => false,
+    }
+}
+
+#[test]
+fn locations_are_evaluated_to_typed_values() {
+    let maps = map_with(locations());
+    let record = &maps[0].stk_map_records[0];
+    let values = record
+        .evaluate_locations(maps[0].constants(), &Registers, &memory)
+        .unwrap();
+    assert_eq!(
+        values,
+        [
+            Value::U32(0x2a),
+            Value::U16(0x0102),
+            Value::Address(RSP_VALUE + 16),
+            Value::U128(0x0011_2233_4455_6677_8899_aabb_ccdd_eeff),
+            Value::U64(u64::MAX),
+            Value::U64(0x1234_5678_9abc),
+        ]
+    );
+
+    let index = StackMapIndex::new(&maps);
+    let indexed = index.record_at(0x1010).unwrap();
+    assert_eq!(
+        indexed.evaluate_locations(&Registers, &memory).unwrap(),
+        values
+    );
+}
+
+#[test]
+fn evaluation_errors_refer_to_the_location() {
+    let mut locations = locations();
+    locations[1] = location(LocationType::Indirect, 8, RSP, 0x100);
+    let maps = map_with(locations);
+    let record = &maps[0].stk_map_records[0];
+    assert_eq!(
+        record.evaluate_locations(maps[0].constants(), &Registers, &memory),
+        Err(DeoptError::Evaluation(
+            1,
+            EvaluationError::MemoryUnreadable(RSP_VALUE + 0x100)
+        ))
+    );
+    // Without the large constants, the ConstIndex location can not be evaluated.
+    let maps = map_with(vec![]);
+    assert_eq!(
+        maps[0].stk_map_records[0].evaluate_locations(&[], &Registers, &memory),
+        Err(DeoptError::Evaluation(
+            0,
+            EvaluationError::ConstIndexOutOfBounds(0)
+        ))
+    );
+}
+
+#[test]
+fn schema_maps_slots_to_locations() {
+    let schema = DeoptSchema::from_names(3, ["vector", "flags"]).with_slot("bci", 1);
+    assert_eq!(
+        schema.slots(),
+        [
+            DeoptSlot {
+                name: "vector".to_owned(),
+                location_idx: 3,
+            },
+            DeoptSlot {
+                name: "flags".to_owned(),
+                location_idx: 4,
+            },
+            DeoptSlot {
+                name: "bci".to_owned(),
+                location_idx: 1,
+            },
+        ]
+    );
+
+    let maps = map_with(locations());
+    let index = StackMapIndex::new(&maps);
+    let record = index.record_at(0x1010).unwrap();
+    let state = schema.extract(&record, &Registers, &memory).unwrap();
+    assert_eq!(
+        state.values,
+        [
+            (
+                "vector".to_owned(),
+                Value::U128(0x0011_2233_4455_6677_8899_aabb_ccdd_eeff)
+            ),
+            ("flags".to_owned(), Value::U64(u64::MAX)),
+            ("bci".to_owned(), Value::U16(0x0102)),
+        ]
+    );
+    assert_eq!(state.get("bci").and_then(|v| v.as_u64()), Some(0x0102));
+    assert_eq!(state.get("flags").map(|v| v.size()), Some(8));
+    assert_eq!(state.get("missing"), None);
+    assert_eq!(
+        DeoptSchema::new()
+            .extract(&record, &Registers, &memory)
+            .unwrap()
+            .values,
+        []
+    );
+}
+
+#[test]
+fn schema_with_more_slots_than_locations_is_rejected() {
+    let maps = map_with(locations());
+    let index = StackMapIndex::new(&maps);
+    let record = index.record_at(0x1010).unwrap();
+    // The record has 6 locations.
+    let schema = DeoptSchema::from_names(4, ["a", "b", "c"]);
+    assert_eq!(
+        schema.extract(&record, &Registers, &memory),
+        Err(DeoptError::MissingLocation {
+            slot: "c".to_owned(),
+            location_idx: 6,
+        })
+    );
+
+    // Only the locations referred to by slots are evaluated.
+    let schema = DeoptSchema::new().with_slot("bci", 1);
+    let mut broken = locations();
+    broken[0] = location(LocationType::Invalid, 8, 0, 0);
+    let maps = map_with(broken);
+    let index = StackMapIndex::new(&maps);
+    let record = index.record_at(0x1010).unwrap();
+    let state = schema.extract(&record, &Registers, &memory).unwrap();
+    assert_eq!(state.get("bci"), Some(Value::U16(0x0102)));
+    assert_eq!(
+        DeoptSchema::new()
+            .with_slot("first", 0)
+            .extract(&record, &Registers, &memory),
+        Err(DeoptError::Evaluation(0, EvaluationError::InvalidLocation))
+    );
+}
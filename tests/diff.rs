@@ -0,0 +1,175 @@
+use llvm_stackmap::{
+    LiveOut, Location, LocationType, StackMap, StackMapChange, StkMapRecord, StkSizeRecord, Symbol,
+    Symbols,
+};
+
+fn function(function_address: u64, stack_size: u64) -> StkSizeRecord {
+    StkSizeRecord {
+        function_address,
+        stack_size,
+        ..Default::default()
+    }
+}
+
+fn record(patch_point_id: u64, instruction_offset: u32) -> StkMapRecord {
+    let location = Location {
+        loc_type: LocationType::Register,
+        loc_size: 8,
+        ..Default::default()
+    };
+    StkMapRecord::new(patch_point_id, instruction_offset, vec![location], vec![]).unwrap()
+}
+
+/// Two functions, where the second contains the patch point ID 7 twice.
+fn build(base: u64, records: Vec<StkMapRecord>) -> StackMap {
+    let mut map = StackMap::default();
+    map.push_function(function(base, 8), vec![record(1, 4)])
+        .unwrap();
+    map.push_function(function(base + 0x40, 16), records)
+        .unwrap();
+    map
+}
+
+#[test]
+fn shifted_functions_are_unchanged() {
+    let old = build(0x1000, vec![record(7, 4), record(7, 12)]);
+    let new = build(0x2000, vec![record(7, 4), record(7, 12)]);
+    assert_eq!(StackMap::diff(&[old], &[new]), vec![]);
+}
+
+#[test]
+fn stack_size_of_shifted_function() {
+    let old = build(0x1000, vec![record(7, 4)]);
+    let mut new = build(0x2000, vec![record(7, 4)]);
+    new.stk_size_records[1].stack_size = 32;
+    assert_eq!(
+        StackMap::diff(&[old], &[new]),
+        vec![StackMapChange::StackSizeChanged {
+            function: "0x1040".to_owned(),
+            old: 16,
+            new: 32,
+        }]
+    );
+}
+
+#[test]
+fn repeated_ids_are_matched_by_occurrence() {
+    let old = build(0x1000, vec![record(7, 4), record(7, 12)]);
+    let new = build(0x1000, vec![record(7, 4), record(7, 16), record(7, 24)]);
+    assert_eq!(
+        StackMap::diff(&[old], &[new]),
+        vec![
+            StackMapChange::Moved {
+                patch_point_id: 7,
+                old_function: "0x1040".to_owned(),
+                old_instruction_offset: 12,
+                new_function: "0x1040".to_owned(),
+                new_instruction_offset: 16,
+            },
+            StackMapChange::Added {
+                patch_point_id: 7,
+                function: "0x1040".to_owned(),
+                instruction_offset: 24,
+            },
+        ]
+    );
+}
+
+#[test]
+fn removed_patch_point() {
+    let old = build(0x1000, vec![record(7, 4), record(9, 12)]);
+    let new = build(0x1000, vec![record(7, 4)]);
+    assert_eq!(
+        StackMap::diff(&[old], &[new]),
+        vec![StackMapChange::Removed {
+            patch_point_id: 9,
+            function: "0x1040".to_owned(),
+            instruction_offset: 12,
+        }]
+    );
+}
+
+#[test]
+fn changed_locations_and_live_outs() {
+    let old = build(0x1000, vec![record(7, 4)]);
+    let mut changed = record(7, 4);
+    changed.locations[0].loc_size = 4;
+    changed.push_live_out(LiveOut::new(3, 8)).unwrap();
+    let new = build(0x1000, vec![changed]);
+    assert_eq!(
+        StackMap::diff(&[old], &[new]),
+        vec![
+            StackMapChange::LocationChanged {
+                patch_point_id: 7,
+                location_idx: 0,
+                old: Some((LocationType::Register, 8)),
+                new: Some((LocationType::Register, 4)),
+            },
+            StackMapChange::LiveOutsChanged {
+                patch_point_id: 7,
+                old: vec![],
+                new: vec![LiveOut::new(3, 8)],
+            },
+        ]
+    );
+}
+
+/// The default ID of `gc.statepoint`s.
+const STATEPOINT_ID: u64 = 0xabcdef00;
+
+/// Functions `a` and `b` at `base`, whose records are located at `offsets`.
+fn build_shared(base: u64, a: &[u32], b: &[(u64, u32)], symbolize: bool) -> StackMap {
+    let mut map = StackMap::default();
+    let a = a
+        .iter()
+        .map(|offset| record(STATEPOINT_ID, *offset))
+        .collect();
+    let b = b.iter().map(|(id, offset)| record(*id, *offset)).collect();
+    map.push_function(function(base, 8), a).unwrap();
+    map.push_function(function(base + 0x40, 8), b).unwrap();
+    if symbolize {
+        let symbols: Symbols = [("a", base), ("b", base + 0x40)]
+            .into_iter()
+            .map(|(name, address)| Symbol {
+                name: name.to_owned(),
+                address,
+                size: 0x40,
+            })
+            .collect();
+        map.symbolize(&symbols);
+    }
+    map
+}
+
+#[test]
+fn shared_ids_are_matched_per_function() {
+    let b = [(STATEPOINT_ID, 4), (STATEPOINT_ID, 12)];
+    for (symbolize, function) in [(true, "a"), (false, "0x1000")] {
+        let old = build_shared(0x1000, &[4], &b, symbolize);
+        let new = build_shared(0x2000, &[4, 8], &b, symbolize);
+        assert_eq!(
+            StackMap::diff(&[old], &[new]),
+            vec![StackMapChange::Added {
+                patch_point_id: STATEPOINT_ID,
+                function: function.to_owned(),
+                instruction_offset: 8,
+            }]
+        );
+    }
+}
+
+#[test]
+fn patch_point_moved_to_another_function() {
+    let old = build_shared(0x1000, &[4, 8], &[(1, 4)], true);
+    let new = build_shared(0x1000, &[4], &[(1, 4), (STATEPOINT_ID, 12)], true);
+    assert_eq!(
+        StackMap::diff(&[old], &[new]),
+        vec![StackMapChange::Moved {
+            patch_point_id: STATEPOINT_ID,
+            old_function: "a".to_owned(),
+            old_instruction_offset: 8,
+            new_function: "b".to_owned(),
+            new_instruction_offset: 12,
+        }]
+    );
+}
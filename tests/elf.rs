@@ -0,0 +1,104 @@
+#![cfg(feature = "from-elf")]
+
+use std::fs;
+
+use llvm_stackmap::{ParsingError, StackMap};
+
+mod common;
+use common::fixture;
+
+/// Offset of the `r_info` field of the first entry of `.rela.dyn` in `stackmaps`,
+/// which relocates the address of the first function of the stackmap.
+const FIRST_RELA_INFO_OFFSET: usize = 0x170 + 8;
+
+#[test]
+fn relative_relocations_are_applied() {
+    let maps = StackMap::from_path(fixture("stackmaps")).unwrap();
+    let addresses = maps[0]
+        .functions()
+        .iter()
+        .map(|function| function.function_address)
+        .collect::<Vec<_>>();
+    assert_eq!(addresses, [0x1a0, 0x1e0]);
+}
+
+#[test]
+fn unsupported_relocation_is_rejected() {
+    let mut bytes = fs::read(fixture("stackmaps")).unwrap();
+    let info = &mut bytes[FIRST_RELA_INFO_OFFSET..FIRST_RELA_INFO_OFFSET + 8];
+    assert_eq!(info, 8u64.to_le_bytes(), "expected R_X86_64_RELATIVE");
+    // R_X86_64_GLOB_DAT
+    info.copy_from_slice(&6u64.to_le_bytes());
+
+    match StackMap::from_bytes(&bytes) {
+        Err(ParsingError::Malformed(msg)) => assert!(msg.contains("Unsupported relocation")),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[cfg(feature = "compressed-sections")]
+#[test]
+fn compressed_sections_are_decompressed() {
+    let expected = StackMap::from_path(fixture("stackmaps")).unwrap();
+    for name in ["stackmaps.zlib", "stackmaps.zstd"] {
+        let maps = StackMap::from_path(fixture(name)).unwrap();
+        assert_eq!(maps.len(), 1, "{}", name);
+        assert_eq!(maps[0].to_string(), expected[0].to_string(), "{}", name);
+    }
+}
+
+#[cfg(feature = "compressed-sections")]
+#[test]
+fn compressed_size_is_not_trusted() {
+    // The compression header claims an uncompressed size of 1 << 62 bytes.
+    match StackMap::from_path(fixture("stackmaps.zstd-huge")) {
+        Err(ParsingError::Malformed(_)) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+/// Write `bytes` to a temporary file named `name` and check whether it has a stackmap.
+fn has_stackmap(name: &str, bytes: &[u8]) -> bool {
+    let path = std::env::temp_dir().join(format!("llvm-stackmap-{}-{}", std::process::id(), name));
+    fs::write(&path, bytes).unwrap();
+    let has_stackmap = StackMap::has_stackmap(&path);
+    fs::remove_file(&path).unwrap();
+    has_stackmap
+}
+
+#[test]
+fn has_stackmap_bounds_header_sizes() {
+    let bytes = fs::read(fixture("stackmaps")).unwrap();
+    assert!(has_stackmap("valid", &bytes));
+
+    let shoff = u64::from_le_bytes(bytes[0x28..0x30].try_into().unwrap()) as usize;
+    let shstrndx = u16::from_le_bytes(bytes[0x3e..0x40].try_into().unwrap()) as usize;
+
+    // e_shnum = 0, so the number of sections is taken from the first section header.
+    let mut huge_count = bytes.clone();
+    huge_count[0x3c..0x3e].copy_from_slice(&0u16.to_le_bytes());
+    huge_count[shoff + 0x20..shoff + 0x28].copy_from_slice(&(1u64 << 58).to_le_bytes());
+    assert!(!has_stackmap("huge-count", &huge_count));
+
+    let mut huge_strtab = bytes.clone();
+    let size = shoff + shstrndx * 64 + 0x20;
+    huge_strtab[size..size + 8].copy_from_slice(&(1u64 << 58).to_le_bytes());
+    assert!(!has_stackmap("huge-strtab", &huge_strtab));
+}
+
+/// Offset of the `r_offset` field of the first entry of `.rela.llvm_stackmaps` in
+/// `stackmaps.o`.
+const FIRST_OBJECT_RELA_OFFSET: usize = 0x2f0;
+
+#[test]
+fn object_relocation_offset_is_bounded() {
+    let mut bytes = fs::read(fixture("stackmaps.o")).unwrap();
+    let r_offset = &mut bytes[FIRST_OBJECT_RELA_OFFSET..FIRST_OBJECT_RELA_OFFSET + 8];
+    assert_eq!(r_offset, 0x10u64.to_le_bytes());
+    r_offset.copy_from_slice(&(u64::MAX - 3).to_le_bytes());
+
+    match StackMap::from_bytes(&bytes) {
+        Err(ParsingError::Malformed(msg)) => assert!(msg.contains("exceeds")),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
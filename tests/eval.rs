@@ -0,0 +1,213 @@
+use llvm_stackmap::{
+    EvaluationError, LiveOut, Location, LocationType, ParsingError, StackMap, Value,
+};
+
+/// The DWARF register numbers of rbx and rsp.
+const RBX: u16 = 3;
+const RSP: u16 = 7;
+
+fn regs(dwarf_regnum: u16) -> Option<u64> {
+    match dwarf_regnum {
+        RBX => Some(0x1122_3344_5566_7788),
+        RSP => Some(0x8000),
+        _ => None,
+    }
+}
+
+/// The stack contains the bytes 0x00, 0x01, ... at rsp (0x8000).
+fn mem(address: u64, buf: &mut [u8]) -> bool {
+    match address
+        .checked_sub(0x8000)
+        .filter(|o| o + buf.len() as u64 <= 64)
+    {
+        Some(offset) => {
+            for (idx, byte) in buf.iter_mut().enumerate() {
+                *byte = offset as u8 + idx as u8;
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+fn location(loc_type: LocationType, loc_size: u16, dwarf_regnum: u16, offset: i32) -> Location {
+    Location {
+        loc_type,
+        loc_size,
+        dwarf_regnum,
+        offset_or_constant: offset,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn locations_are_evaluated() {
+    let constants = [0xdead_beef_cafe];
+    let cases = [
+        (
+            location(LocationType::Register, 8, RBX, 0),
+            0x1122_3344_5566_7788,
+        ),
+        (location(LocationType::Register, 4, RBX, 0), 0x5566_7788),
+        (location(LocationType::Register, 1, RBX, 0), 0x88),
+        (location(LocationType::Direct, 8, RSP, -16), 0x7ff0),
+        (
+            location(LocationType::Indirect, 8, RSP, 8),
+            0x0f0e_0d0c_0b0a_0908,
+        ),
+        (location(LocationType::Indirect, 2, RSP, 1), 0x0201),
+        (location(LocationType::Constant, 8, 0, -1), u64::MAX),
+        (
+            location(LocationType::ConstIndex, 8, 0, 0),
+            0xdead_beef_cafe,
+        ),
+    ];
+    for (loc, value) in cases {
+        assert_eq!(
+            loc.evaluate(&constants, &regs, &mem),
+            Ok(value),
+            "{:?}",
+            loc
+        );
+    }
+}
+
+#[test]
+fn location_evaluation_errors() {
+    let cases = [
+        (
+            location(LocationType::Register, 8, 4, 0),
+            EvaluationError::RegisterUnavailable(4),
+        ),
+        (
+            location(LocationType::Indirect, 8, RSP, -8),
+            EvaluationError::MemoryUnreadable(0x7ff8),
+        ),
+        (
+            location(LocationType::Invalid, 8, 0, 0),
+            EvaluationError::InvalidLocation,
+        ),
+        (
+            location(LocationType::ConstIndex, 8, 0, 1),
+            EvaluationError::ConstIndexOutOfBounds(1),
+        ),
+        (
+            location(LocationType::ConstIndex, 8, 0, -1),
+            EvaluationError::ConstIndexOutOfBounds(-1),
+        ),
+        (
+            location(LocationType::Register, 3, RBX, 0),
+            EvaluationError::UnsupportedSize(3),
+        ),
+        // Values larger than 8 bytes require `evaluate_value`.
+        (
+            location(LocationType::Indirect, 16, RSP, 0),
+            EvaluationError::UnsupportedSize(16),
+        ),
+    ];
+    for (loc, error) in cases {
+        assert_eq!(loc.evaluate(&[0], &regs, &mem), Err(error), "{:?}", loc);
+    }
+}
+
+#[test]
+fn location_values_are_typed_by_size() {
+    let cases = [
+        (location(LocationType::Register, 1, RBX, 0), Value::U8(0x88)),
+        (
+            location(LocationType::Register, 2, RBX, 0),
+            Value::U16(0x7788),
+        ),
+        (
+            location(LocationType::Indirect, 4, RSP, 0),
+            Value::U32(0x0302_0100),
+        ),
+        (
+            location(LocationType::Indirect, 16, RSP, 0),
+            Value::U128(0x0f0e_0d0c_0b0a_0908_0706_0504_0302_0100),
+        ),
+        (
+            location(LocationType::Direct, 8, RSP, 8),
+            Value::Address(0x8008),
+        ),
+        (
+            location(LocationType::Constant, 4, 0, -1),
+            Value::U32(u32::MAX),
+        ),
+        (location(LocationType::ConstIndex, 8, 0, 0), Value::U64(42)),
+    ];
+    for (loc, value) in cases {
+        assert_eq!(
+            loc.evaluate_value(&[42], &regs, &mem),
+            Ok(value),
+            "{:?}",
+            loc
+        );
+    }
+
+    let mut ymm = [0u8; 32];
+    ymm.iter_mut()
+        .enumerate()
+        .for_each(|(idx, b)| *b = idx as u8);
+    let value = location(LocationType::Indirect, 32, RSP, 0).evaluate_value(&[], &regs, &mem);
+    assert_eq!(value, Ok(Value::U256(ymm)));
+    assert_eq!(value.unwrap().as_u64(), None);
+    assert_eq!(Value::U128(7).as_u64(), Some(7));
+
+    for size in [3, 64] {
+        assert_eq!(
+            location(LocationType::Indirect, size, RSP, 0).evaluate_value(&[], &regs, &mem),
+            Err(EvaluationError::UnsupportedSize(size))
+        );
+    }
+    // The default `read_register_bytes` can not provide more than 8 bytes.
+    assert_eq!(
+        location(LocationType::Register, 16, RBX, 0).evaluate_value(&[], &regs, &mem),
+        Err(EvaluationError::RegisterUnavailable(RBX))
+    );
+}
+
+#[test]
+fn live_outs_are_evaluated() {
+    assert_eq!(
+        LiveOut::new(RBX, 8).evaluate(&regs),
+        Ok(0x1122_3344_5566_7788)
+    );
+    assert_eq!(LiveOut::new(RBX, 2).evaluate(&regs), Ok(0x7788));
+    assert_eq!(
+        LiveOut::new(4, 8).evaluate(&regs),
+        Err(EvaluationError::RegisterUnavailable(4))
+    );
+    assert_eq!(
+        LiveOut::new(RBX, 16).evaluate(&regs),
+        Err(EvaluationError::UnsupportedSize(16))
+    );
+
+    assert_eq!(
+        LiveOut::new(RBX, 4).evaluate_value(&regs),
+        Ok(Value::U32(0x5566_7788))
+    );
+    assert_eq!(
+        LiveOut::new(RBX, 3).evaluate_value(&regs),
+        Err(EvaluationError::UnsupportedSize(3))
+    );
+    assert_eq!(
+        LiveOut::new(RBX, 64).evaluate_value(&regs),
+        Err(EvaluationError::UnsupportedSize(64))
+    );
+}
+
+#[test]
+fn version_is_parsed() {
+    assert_eq!(StackMap::default().version(), 3);
+
+    let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let maps = StackMap::new(&mut data).unwrap();
+    assert_eq!(maps[0].version(), 3);
+
+    let mut data = vec![2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    match StackMap::new(&mut data) {
+        Err(ParsingError::VersionNotSupported(2)) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
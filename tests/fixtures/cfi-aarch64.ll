@@ -0,0 +1,4 @@
+define i64 @leaf(i64 %x) nounwind {
+  %y = add i64 %x, 1
+  ret i64 %y
+}
@@ -0,0 +1,52 @@
+#!/usr/bin/env python3
+"""Compress the .llvm_stackmaps section of a 64-bit little endian ELF file.
+
+usage: compress_section.py <input> <output> zlib|zstd [ch_size]
+
+The compressed content is appended to the file and the section header is
+updated to point to it. If `ch_size` is given, it is stored as the
+uncompressed size in the compression header instead of the actual size.
+"""
+import struct
+import subprocess
+import sys
+import zlib
+
+SHF_COMPRESSED = 0x800
+SHDR = '<IIQQQQIIQQ'
+ELFCOMPRESS = {'zlib': 1, 'zstd': 2}
+
+src, dst, kind = sys.argv[1:4]
+data = bytearray(open(src, 'rb').read())
+shoff, = struct.unpack_from('<Q', data, 0x28)
+shentsize, shnum, shstrndx = struct.unpack_from('<HHH', data, 0x3a)
+
+
+def header(idx):
+    return list(struct.unpack_from(SHDR, data, shoff + idx * shentsize))
+
+
+strtab = header(shstrndx)[4]
+for idx in range(shnum):
+    shdr = header(idx)
+    name_start = strtab + shdr[0]
+    if data[name_start:data.index(b'\0', name_start)] == b'.llvm_stackmaps':
+        break
+else:
+    sys.exit('no .llvm_stackmaps section')
+
+raw = bytes(data[shdr[4]:shdr[4] + shdr[5]])
+if kind == 'zlib':
+    compressed = zlib.compress(raw)
+else:
+    compressed = subprocess.run(['zstd', '-q', '-c'], input=raw, stdout=subprocess.PIPE,
+                                check=True).stdout
+ch_size = int(sys.argv[4], 0) if len(sys.argv) > 4 else len(raw)
+blob = struct.pack('<IIQQ', ELFCOMPRESS[kind], 0, ch_size, 8) + compressed
+
+shdr[2] |= SHF_COMPRESSED
+shdr[4] = len(data)
+shdr[5] = len(blob)
+data += blob
+struct.pack_into(SHDR, data, shoff + idx * shentsize, *shdr)
+open(dst, 'wb').write(data)
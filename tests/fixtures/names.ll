@@ -0,0 +1,12 @@
+declare void @llvm.experimental.stackmap(i64, i32, ...)
+
+define i32 @_Z3fooii(i32 %a, i32 %b) {
+  %c = add i32 %a, %b
+  call void (i64, i32, ...) @llvm.experimental.stackmap(i64 1, i32 0, i32 %c)
+  ret i32 %c
+}
+
+define i32 @"say \22hi\22"(i32 %a) {
+  call void (i64, i32, ...) @llvm.experimental.stackmap(i64 2, i32 0, i32 %a)
+  ret i32 %a
+}
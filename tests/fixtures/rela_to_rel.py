@@ -0,0 +1,60 @@
+#!/usr/bin/env python3
+"""Convert the RELA dynamic relocations of a 64-bit little endian ELF file to REL.
+
+usage: rela_to_rel.py <input> <output>
+
+The `.rela.dyn` section is renamed to `.rel.dyn` and its entries are rewritten
+in place, with their addends stored at the relocated locations. The dynamic
+section is updated to refer to the REL relocations.
+"""
+import struct
+import sys
+
+SHDR = '<IIQQQQIIQQ'
+SHT_RELA, SHT_REL, SHT_DYNAMIC = 4, 9, 6
+DT_RENAMES = {7: 17, 8: 18, 9: 19, 0x6ffffff9: 0x6ffffffa}
+
+src, dst = sys.argv[1:3]
+data = bytearray(open(src, 'rb').read())
+shoff, = struct.unpack_from('<Q', data, 0x28)
+shentsize, shnum, shstrndx = struct.unpack_from('<HHH', data, 0x3a)
+
+
+def header(idx):
+    return list(struct.unpack_from(SHDR, data, shoff + idx * shentsize))
+
+
+def file_offset(address):
+    for idx in range(shnum):
+        shdr = header(idx)
+        if shdr[3] <= address < shdr[3] + shdr[5] and shdr[1] != 8:
+            return shdr[4] + address - shdr[3]
+    sys.exit('address %#x not in file' % address)
+
+
+strtab = header(shstrndx)[4]
+for idx in range(shnum):
+    shdr = header(idx)
+    name_start = strtab + shdr[0]
+    name = data[name_start:data.index(b'\0', name_start)]
+    if shdr[1] == SHT_RELA and name == b'.rela.dyn':
+        count = shdr[5] // 24
+        relocs = [struct.unpack_from('<QQq', data, shdr[4] + i * 24) for i in range(count)]
+        data[shdr[4]:shdr[4] + shdr[5]] = bytes(shdr[5])
+        for i, (r_offset, r_info, r_addend) in enumerate(relocs):
+            struct.pack_into('<QQ', data, shdr[4] + i * 16, r_offset, r_info)
+            struct.pack_into('<q', data, file_offset(r_offset), r_addend)
+        data[name_start:name_start + len(name) + 1] = b'.rel.dyn\0\0'
+        shdr[1], shdr[5], shdr[9] = SHT_REL, count * 16, 16
+        struct.pack_into(SHDR, data, shoff + idx * shentsize, *shdr)
+        rel_size = count * 16
+    elif shdr[1] == SHT_DYNAMIC:
+        dynamic = shdr
+
+for i in range(dynamic[5] // 16):
+    tag, value = struct.unpack_from('<qQ', data, dynamic[4] + i * 16)
+    if tag in DT_RENAMES:
+        value = {8: rel_size, 9: 16}.get(tag, value)
+        struct.pack_into('<qQ', data, dynamic[4] + i * 16, DT_RENAMES[tag], value)
+
+open(dst, 'wb').write(data)
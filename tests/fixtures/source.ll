@@ -0,0 +1,30 @@
+declare void @llvm.experimental.stackmap(i64, i32, ...)
+
+define i64 @foo(i64 %a) !dbg !6 {
+  %b = add i64 %a, 1, !dbg !10
+  call void (i64, i32, ...) @llvm.experimental.stackmap(i64 1, i32 0, i64 %b), !dbg !11
+  %c = mul i64 %b, %a, !dbg !12
+  ret i64 %c, !dbg !16
+}
+
+define i32 @main() !dbg !13 {
+  %r = call i64 @foo(i64 3), !dbg !14
+  ret i32 0, !dbg !15
+}
+
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!3, !4}
+
+!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: "hand-written", isOptimized: true, runtimeVersion: 0, emissionKind: FullDebug)
+!1 = !DIFile(filename: "source.c", directory: "/src")
+!3 = !{i32 7, !"Dwarf Version", i32 4}
+!4 = !{i32 2, !"Debug Info Version", i32 3}
+!5 = !DISubroutineType(types: !{})
+!6 = distinct !DISubprogram(name: "foo", scope: !1, file: !1, line: 3, type: !5, scopeLine: 3, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !0)
+!10 = !DILocation(line: 4, column: 9, scope: !6)
+!11 = !DILocation(line: 5, column: 3, scope: !6)
+!12 = !DILocation(line: 6, column: 3, scope: !6)
+!13 = distinct !DISubprogram(name: "main", scope: !1, file: !1, line: 9, type: !5, scopeLine: 9, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !0)
+!14 = !DILocation(line: 10, column: 3, scope: !13)
+!15 = !DILocation(line: 11, column: 3, scope: !13)
+!16 = !DILocation(line: 7, column: 3, scope: !6)
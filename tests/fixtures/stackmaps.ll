@@ -0,0 +1,24 @@
+declare void @llvm.experimental.stackmap(i64, i32, ...)
+declare void @llvm.experimental.patchpoint.void(i64, i32, i8*, i32, ...)
+
+define i64 @foo(i64 %a, i64 %b, i32* %p) {
+entry:
+  %x = alloca i64
+  store i64 %a, i64* %x
+  %c = add i64 %a, %b
+  call void (i64, i32, ...) @llvm.experimental.stackmap(i64 1, i32 8, i64 %a, i64 %c, i64 12345678901234, i32 7, i64* %x, i32* %p)
+  call void (i64, i32, i8*, i32, ...) @llvm.experimental.patchpoint.void(i64 2, i32 16, i8* null, i32 0, i64 %c, i64 -5)
+  %r = mul i64 %c, %b
+  ret i64 %r
+}
+
+define i32 @bar(i32 %a) {
+  %b = add i32 %a, 1
+  call void (i64, i32, ...) @llvm.experimental.stackmap(i64 3, i32 0, i32 %b, i64 99999999999)
+  ret i32 %b
+}
+
+define i32 @main() {
+  %r = call i32 @bar(i32 3)
+  ret i32 %r
+}
@@ -0,0 +1,11 @@
+declare void @callee()
+define i8 addrspace(1)* @test(i8 addrspace(1)* %obj, i64 %x) gc "statepoint-example" {
+  %d = getelementptr i8, i8 addrspace(1)* %obj, i64 16
+  %tok = call token (i64, i32, void ()*, i32, i32, ...) @llvm.experimental.gc.statepoint.p0f_isVoidf(i64 42, i32 0, void ()* elementtype(void ()) @callee, i32 0, i32 1, i32 0, i32 0) ["deopt"(i64 %x, i32 7), "gc-live"(i8 addrspace(1)* %obj, i8 addrspace(1)* %d)]
+  %obj2 = call i8 addrspace(1)* @llvm.experimental.gc.relocate.p1i8(token %tok, i32 0, i32 0)
+  %d2 = call i8 addrspace(1)* @llvm.experimental.gc.relocate.p1i8(token %tok, i32 0, i32 1)
+  store i8 0, i8 addrspace(1)* %d2
+  ret i8 addrspace(1)* %obj2
+}
+declare token @llvm.experimental.gc.statepoint.p0f_isVoidf(i64, i32, void ()*, i32, i32, ...)
+declare i8 addrspace(1)* @llvm.experimental.gc.relocate.p1i8(token, i32, i32)
@@ -0,0 +1,119 @@
+use std::fs;
+
+use llvm_stackmap::{BinaryFormat, ParsingError, StackMap};
+
+mod common;
+use common::fixture;
+
+/// Get the (name, address) of the functions of `maps`.
+fn functions(maps: &[StackMap]) -> Vec<(Option<&str>, u64)> {
+    maps.iter()
+        .flat_map(|map| {
+            map.functions()
+                .iter()
+                .map(move |f| (map.function_name(f), f.function_address))
+        })
+        .collect()
+}
+
+/// Detect the format of the fixture `name` and parse it via both `from_bytes` and
+/// `from_reader`, which must agree.
+fn parse(name: &str, format: BinaryFormat) -> Vec<StackMap> {
+    let bytes = fs::read(fixture(name)).unwrap();
+    assert_eq!(BinaryFormat::detect(&bytes), format, "{}", name);
+    let maps = StackMap::from_bytes(&bytes).unwrap();
+    let from_reader = StackMap::from_reader(fs::File::open(fixture(name)).unwrap()).unwrap();
+    assert_eq!(functions(&from_reader), functions(&maps), "{}", name);
+    maps
+}
+
+#[cfg(feature = "from-elf")]
+#[test]
+fn elf_is_detected() {
+    let maps = parse("stackmaps", BinaryFormat::Elf);
+    assert_eq!(
+        functions(&maps),
+        [(Some("foo"), 0x1a0), (Some("bar"), 0x1e0)]
+    );
+}
+
+#[cfg(feature = "from-macho")]
+#[test]
+fn macho_is_detected() {
+    let maps = parse("stackmaps-x86_64.o", BinaryFormat::MachO);
+    assert_eq!(functions(&maps), [(Some("foo"), 0x0), (Some("bar"), 0x40)]);
+
+    // The slice of the host architecture is selected from universal binaries.
+    let bar = match llvm_stackmap::Arch::host() {
+        Some(llvm_stackmap::Arch::X86_64) => 0x40,
+        Some(llvm_stackmap::Arch::AArch64) => 0x48,
+        None => return,
+    };
+    let maps = parse("stackmaps-fat.o", BinaryFormat::MachO);
+    assert_eq!(functions(&maps), [(Some("foo"), 0x0), (Some("bar"), bar)]);
+}
+
+#[cfg(feature = "from-pe")]
+#[test]
+fn coff_and_pe_are_detected() {
+    let maps = parse("stackmaps.obj", BinaryFormat::Pe);
+    assert_eq!(functions(&maps), [(Some("foo"), 0x0), (Some("bar"), 0x40)]);
+
+    let maps = parse("stackmaps.dll", BinaryFormat::Pe);
+    assert_eq!(
+        functions(&maps),
+        [(Some("foo"), 0x1000), (Some("bar"), 0x1040)]
+    );
+}
+
+#[cfg(all(feature = "from-elf", feature = "from-macho"))]
+#[test]
+fn archive_is_detected() {
+    // The stackmaps of the ELF and the Mach-O member.
+    let maps = parse("stackmaps.a", BinaryFormat::Archive);
+    assert_eq!(
+        functions(&maps),
+        [
+            (Some("foo"), 0x0),
+            (Some("bar"), 0x40),
+            (Some("foo"), 0x0),
+            (Some("bar"), 0x40)
+        ]
+    );
+}
+
+#[test]
+fn raw_section_is_detected() {
+    let mut bytes = vec![3, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    // One function at 0x1000 without records.
+    bytes.extend_from_slice(&0x1000u64.to_le_bytes());
+    bytes.extend_from_slice(&16u64.to_le_bytes());
+    bytes.extend_from_slice(&0u64.to_le_bytes());
+    assert_eq!(BinaryFormat::detect(&bytes), BinaryFormat::Raw);
+    assert_eq!(
+        functions(&StackMap::from_bytes(&bytes).unwrap()),
+        [(None, 0x1000)]
+    );
+    let from_reader = StackMap::from_reader(bytes.as_slice()).unwrap();
+    assert_eq!(functions(&from_reader), [(None, 0x1000)]);
+}
+
+#[test]
+fn unknown_input_is_rejected() {
+    let bytes = b"neither a binary nor a stack map";
+    assert_eq!(BinaryFormat::detect(bytes), BinaryFormat::Raw);
+    match StackMap::from_bytes(bytes) {
+        Err(ParsingError::VersionNotSupported(b'n')) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+    match StackMap::from_reader(&bytes[..]) {
+        Err(ParsingError::VersionNotSupported(b'n')) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+
+    // A truncated header is not a valid stack map either.
+    match StackMap::from_bytes(&[3, 0]) {
+        Err(ParsingError::Malformed(_)) => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
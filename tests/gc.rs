@@ -0,0 +1,210 @@
+use llvm_stackmap::{
+    EvaluationError, GcRoot, GcRootError, Location, LocationType, MemoryProvider, StackMap,
+    StackMapIndex, StatepointError, StkMapRecord, StkSizeRecord,
+};
+
+mod common;
+
+/// The DWARF register number of rsp.
+const RSP: u16 = 7;
+
+/// The stack of a stopped thread, starting at `start`.
+struct Stack {
+    start: u64,
+    bytes: Vec<u8>,
+}
+
+impl Stack {
+    fn new(start: u64, size: usize) -> Stack {
+        Stack {
+            start,
+            bytes: vec![0; size],
+        }
+    }
+
+    fn range(&self, address: u64, len: usize) -> Option<std::ops::Range<usize>> {
+        let offset = usize::try_from(address.checked_sub(self.start)?).ok()?;
+        let end = offset.checked_add(len)?;
+        (end <= self.bytes.len()).then_some(offset..end)
+    }
+
+    fn read_u64(&self, address: u64) -> u64 {
+        let mut buf = [0u8; 8];
+        assert!(self.read_memory(address, &mut buf));
+        u64::from_le_bytes(buf)
+    }
+
+    fn write_u64(&mut self, address: u64, value: u64) {
+        let range = self.range(address, 8).unwrap();
+        self.bytes[range].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+impl MemoryProvider for Stack {
+    fn read_memory(&self, address: u64, buf: &mut [u8]) -> bool {
+        match self.range(address, buf.len()) {
+            Some(range) => {
+                buf.copy_from_slice(&self.bytes[range]);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn location(loc_type: LocationType, dwarf_regnum: u16, offset_or_constant: i32) -> Location {
+    Location {
+        loc_type,
+        loc_size: 8,
+        dwarf_regnum,
+        offset_or_constant,
+        ..Default::default()
+    }
+}
+
+fn constant(value: i32) -> Location {
+    location(LocationType::Constant, 0, value)
+}
+
+fn spilled(offset: i32) -> Location {
+    location(LocationType::Indirect, RSP, offset)
+}
+
+/// A map with a single statepoint at 0x1010 whose GC pointer pairs are `pairs`.
+fn statepoint_map(pairs: &[(Location, Location)]) -> Vec<StackMap> {
+    let mut locations = vec![constant(0), constant(0), constant(0)];
+    locations.extend(pairs.iter().flat_map(|(base, derived)| [*base, *derived]));
+    let record = StkMapRecord::new(7, 0x10, locations, vec![]).unwrap();
+    let function = StkSizeRecord {
+        function_address: 0x1000,
+        stack_size: 48,
+        ..Default::default()
+    };
+    let mut map = StackMap::default();
+    map.push_function(function, vec![record]).unwrap();
+    vec![map]
+}
+
+#[cfg(feature = "from-elf")]
+#[test]
+fn gc_roots_of_object_are_found() {
+    let maps = StackMap::from_path(common::fixture("statepoint.o")).unwrap();
+    let index = StackMapIndex::new(&maps);
+    let function = maps[0].stk_size_records[0];
+    let return_address = maps[0].stk_map_records[0].address(&function);
+
+    let rsp = 0x7fff_0000;
+    let regs = |regnum| (regnum == RSP).then_some(rsp);
+    let roots = index.gc_roots(return_address, &regs).unwrap();
+    // The pointer is spilled to [rsp+8] and not derived.
+    assert_eq!(
+        roots,
+        [GcRoot {
+            base_slot: rsp + 8,
+            derived_slot: rsp + 8,
+        }]
+    );
+    assert!(!roots[0].is_derived());
+
+    assert_eq!(
+        index.gc_roots(return_address, &|_| None),
+        Err(GcRootError::Evaluation(
+            EvaluationError::RegisterUnavailable(RSP)
+        ))
+    );
+    assert_eq!(
+        index.gc_roots(return_address + 1, &regs),
+        Err(GcRootError::NoRecord(return_address + 1))
+    );
+}
+
+#[test]
+fn derived_pointers_are_relocated() {
+    // A pointer to an object, a pointer 0x18 bytes into it, a pointer before
+    // it, and a null pointer.
+    let maps = statepoint_map(&[
+        (spilled(0), spilled(0)),
+        (spilled(0), spilled(8)),
+        (spilled(0), spilled(16)),
+        (constant(0), constant(0)),
+    ]);
+    let index = StackMapIndex::new(&maps);
+    let rsp = 0x8000;
+    let regs = |regnum| (regnum == RSP).then_some(rsp);
+    let mut stack = Stack::new(rsp, 24);
+    stack.write_u64(rsp, 0x5000);
+    stack.write_u64(rsp + 8, 0x5018);
+    stack.write_u64(rsp + 16, 0x4ff0);
+
+    let roots = index.gc_roots(0x1010, &regs).unwrap();
+    assert_eq!(
+        roots
+            .iter()
+            .map(|root| (root.base_slot, root.derived_slot, root.is_derived()))
+            .collect::<Vec<_>>(),
+        [
+            (rsp, rsp, false),
+            (rsp, rsp + 8, true),
+            (rsp, rsp + 16, true)
+        ]
+    );
+
+    // Move the object to 0x9000. All derived pointers are computed before the
+    // shared base slot is updated.
+    let new_base = 0x9000;
+    let updates = roots
+        .iter()
+        .map(|root| {
+            let old_base = stack.read_u64(root.base_slot);
+            let old_derived = stack.read_u64(root.derived_slot);
+            let new_derived = GcRoot::relocate_derived(old_base, old_derived, new_base);
+            (root.derived_slot, new_derived)
+        })
+        .collect::<Vec<_>>();
+    for (slot, value) in updates {
+        stack.write_u64(slot, value);
+    }
+    assert_eq!(stack.read_u64(rsp), 0x9000);
+    assert_eq!(stack.read_u64(rsp + 8), 0x9018);
+    assert_eq!(stack.read_u64(rsp + 16), 0x8ff0);
+}
+
+#[test]
+fn relocate_derived_preserves_offset() {
+    assert_eq!(GcRoot::relocate_derived(0x1000, 0x1000, 0x2000), 0x2000);
+    assert_eq!(GcRoot::relocate_derived(0x1000, 0x1010, 0x2000), 0x2010);
+    assert_eq!(GcRoot::relocate_derived(0x1000, 0xff8, 0x2000), 0x1ff8);
+    assert_eq!(GcRoot::relocate_derived(0x1000, 0x1010, u64::MAX), 0xf);
+}
+
+#[test]
+fn gc_pointers_not_in_memory_are_rejected() {
+    let regs = |regnum| (regnum == RSP).then_some(0x8000);
+    let register = location(LocationType::Register, 3, 0);
+    let cases = [
+        // The locations of the pair are #4 and #5.
+        ((register, spilled(0)), GcRootError::NotInMemory(3)),
+        ((spilled(0), register), GcRootError::NotInMemory(4)),
+        ((constant(0), spilled(0)), GcRootError::NotInMemory(3)),
+        (
+            (location(LocationType::Invalid, 0, 0), spilled(0)),
+            GcRootError::Evaluation(EvaluationError::InvalidLocation),
+        ),
+    ];
+    for (pair, error) in cases {
+        let maps = statepoint_map(&[pair]);
+        let index = StackMapIndex::new(&maps);
+        assert_eq!(index.gc_roots(0x1010, &regs), Err(error));
+    }
+
+    let mut maps = statepoint_map(&[]);
+    maps[0].stk_map_records[0].locations.truncate(2);
+    maps[0].stk_map_records[0].num_locations = 2;
+    let index = StackMapIndex::new(&maps);
+    assert_eq!(
+        index.gc_roots(0x1010, &regs),
+        Err(GcRootError::NotAStatepoint(StatepointError::MissingHeader(
+            2
+        )))
+    );
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "from-macho")]
+
+use llvm_stackmap::{Arch, ParseOptions, ParsingError, StackMap};
+
+mod common;
+use common::fixture;
+
+/// Get (name, address, stack size) of all functions of `map`.
+fn functions(map: &StackMap) -> Vec<(Option<&str>, u64, u64)> {
+    map.functions()
+        .iter()
+        .map(|f| (map.function_name(f), f.function_address, f.stack_size))
+        .collect()
+}
+
+#[test]
+fn object_is_relocated() {
+    let maps =
+        StackMap::from_macho_path(fixture("stackmaps-x86_64.o"), &ParseOptions::default()).unwrap();
+    assert_eq!(maps.len(), 1);
+    assert_eq!(
+        functions(&maps[0]),
+        [(Some("foo"), 0x0, 40), (Some("bar"), 0x40, 8)]
+    );
+}
+
+#[test]
+fn fat_slice_is_selected_by_arch() {
+    let path = fixture("stackmaps-fat.o");
+    let x86_64 = ParseOptions::default().with_arch(Arch::X86_64);
+    let maps = StackMap::from_macho_path(&path, &x86_64).unwrap();
+    assert_eq!(
+        functions(&maps[0]),
+        [(Some("foo"), 0x0, 40), (Some("bar"), 0x40, 8)]
+    );
+
+    let aarch64 = ParseOptions::default().with_arch(Arch::AArch64);
+    let maps = StackMap::from_macho_path(&path, &aarch64).unwrap();
+    assert_eq!(
+        functions(&maps[0]),
+        [(Some("foo"), 0x0, 48), (Some("bar"), 0x48, 16)]
+    );
+}
+
+#[test]
+fn thin_file_ignores_arch() {
+    let options = ParseOptions::default().with_arch(Arch::X86_64);
+    let maps = StackMap::from_macho_path(fixture("stackmaps-arm64.o"), &options).unwrap();
+    assert_eq!(maps[0].functions()[0].stack_size, 48);
+}
+
+#[test]
+fn section_is_found_by_qualified_name() {
+    let path = fixture("stackmaps-x86_64.o");
+    let options = ParseOptions::new(["__LLVM_STACKMAPS,__llvm_stackmaps"]);
+    assert_eq!(StackMap::from_macho_path(&path, &options).unwrap().len(), 1);
+
+    let options = ParseOptions::new(["__DATA,__llvm_stackmaps"]);
+    assert!(matches!(
+        StackMap::from_macho_path(&path, &options),
+        Err(ParsingError::StackMapSectionNotFound)
+    ));
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "mmap")]
+
+use std::fs;
+
+use llvm_stackmap::{ParseOptions, ParsingError, StackMap};
+
+mod common;
+use common::fixture;
+
+#[test]
+fn mmap_and_read_give_equal_results() {
+    for name in [
+        "stackmaps",
+        "stackmaps.rel",
+        "stackmaps.stripped",
+        "stackmaps.zlib",
+        "stackmaps.zstd",
+        "stackmaps.o",
+        "statepoint.o",
+    ] {
+        let expected = StackMap::from_path(fixture(name)).unwrap();
+        let maps = StackMap::from_path_mmap(fixture(name), &ParseOptions::default()).unwrap();
+        assert_eq!(maps.len(), expected.len(), "{}", name);
+        for (map, expected) in maps.iter().zip(expected.iter()) {
+            assert_eq!(map.to_string(), expected.to_string(), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn rel_relocations_are_processed() {
+    // The r_info field of the first entry of `.rel.dyn`, R_X86_64_RELATIVE.
+    const FIRST_REL_INFO_OFFSET: usize = 0x170 + 8;
+    let mut bytes = fs::read(fixture("stackmaps.rel")).unwrap();
+    let info = &mut bytes[FIRST_REL_INFO_OFFSET..FIRST_REL_INFO_OFFSET + 8];
+    assert_eq!(info, 8u64.to_le_bytes());
+    // R_X86_64_GLOB_DAT
+    info.copy_from_slice(&6u64.to_le_bytes());
+
+    let path = std::env::temp_dir().join(format!("llvm-stackmap-{}-rel", std::process::id()));
+    fs::write(&path, &bytes).unwrap();
+    let from_path = StackMap::from_path(&path);
+    let from_mmap = StackMap::from_path_mmap(&path, &ParseOptions::default());
+    fs::remove_file(&path).unwrap();
+    for res in [from_path, from_mmap] {
+        match res {
+            Err(ParsingError::Malformed(msg)) => assert!(msg.contains("Unsupported relocation")),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use llvm_stackmap::{Location, StackMap, StkMapRecord, StkSizeRecord};
+
+fn record(patch_point_id: u64, locations: usize) -> StkMapRecord {
+    StkMapRecord {
+        patch_point_id,
+        locations: vec![Location::default(); locations],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn push_function_keeps_counts_in_sync() {
+    let mut map = StackMap::default();
+    map.push_function(StkSizeRecord::default(), vec![record(1, 2), record(2, 0)])
+        .unwrap();
+    map.push_function(StkSizeRecord::default(), vec![record(3, 1)])
+        .unwrap();
+
+    assert_eq!(map.num_functions, 2);
+    assert_eq!(map.num_records, 3);
+    assert_eq!(map.stk_size_records[0].record_count, 2);
+    assert_eq!(map.stk_size_records[1].record_count, 1);
+    assert_eq!(map.stk_map_records[0].num_locations, 2);
+    assert_eq!(map.stk_map_records[2].num_locations, 1);
+}
+
+#[test]
+fn push_function_validates_before_modifying() {
+    let mut map = StackMap::default();
+    map.push_function(StkSizeRecord::default(), vec![record(1, 1)])
+        .unwrap();
+
+    let too_many_locations = record(2, usize::from(u16::MAX) + 1);
+    let result = map.push_function(
+        StkSizeRecord::default(),
+        vec![record(3, 1), too_many_locations],
+    );
+    assert!(result.is_err());
+    assert_eq!(map.function_count(), 1);
+    assert_eq!(map.record_count(), 1);
+    assert_eq!(map.num_functions, 1);
+    assert_eq!(map.num_records, 1);
+}
+
+#[test]
+fn sync_counts_validates_before_modifying() {
+    let mut map = StackMap::default();
+    map.stk_map_records.push(record(1, 1));
+    map.stk_map_records
+        .push(record(2, usize::from(u16::MAX) + 1));
+
+    assert!(map.sync_counts().is_err());
+    assert_eq!(map.num_records, 0);
+    assert_eq!(map.stk_map_records[0].num_locations, 0);
+}
@@ -0,0 +1,159 @@
+use llvm_stackmap::{Arch, CodeWriter, MemoryProvider, PatchError, PatchSite};
+
+mod common;
+
+/// Code located at `address`.
+struct Memory {
+    address: u64,
+    bytes: Vec<u8>,
+}
+
+impl Memory {
+    fn range(&self, address: u64, len: usize) -> Option<std::ops::Range<usize>> {
+        let start = usize::try_from(address.checked_sub(self.address)?).ok()?;
+        let end = start.checked_add(len)?;
+        (end <= self.bytes.len()).then_some(start..end)
+    }
+}
+
+impl MemoryProvider for Memory {
+    fn read_memory(&self, address: u64, buf: &mut [u8]) -> bool {
+        match self.range(address, buf.len()) {
+            Some(range) => {
+                buf.copy_from_slice(&self.bytes[range]);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl CodeWriter for Memory {
+    fn write_code(&mut self, address: u64, bytes: &[u8]) -> bool {
+        match self.range(address, bytes.len()) {
+            Some(range) => {
+                self.bytes[range].copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn site(address: u64, shadow_size: usize) -> PatchSite {
+    PatchSite {
+        address,
+        shadow_size,
+    }
+}
+
+#[test]
+fn rel32_is_relative_to_the_next_instruction() {
+    let site = site(0x1000, 8);
+    assert_eq!(site.x86_64_call(0x1005), Some([0xe8, 0, 0, 0, 0]));
+    assert_eq!(
+        site.x86_64_call(0x2005),
+        Some([0xe8, 0x00, 0x10, 0x00, 0x00])
+    );
+    assert_eq!(
+        site.x86_64_jmp(0x1000),
+        Some([0xe9, 0xfb, 0xff, 0xff, 0xff])
+    );
+
+    let max = 0x1005 + i32::MAX as u64;
+    assert_eq!(site.x86_64_jmp(max), Some([0xe9, 0xff, 0xff, 0xff, 0x7f]));
+    assert_eq!(site.x86_64_jmp(max + 1), None);
+    let min = 0x1005u64.wrapping_sub(1 << 31);
+    assert_eq!(site.x86_64_call(min), Some([0xe8, 0x00, 0x00, 0x00, 0x80]));
+    assert_eq!(site.x86_64_call(min.wrapping_sub(1)), None);
+}
+
+#[test]
+fn patch_is_padded_and_restored() {
+    let mut mem = Memory {
+        address: 0x11000,
+        bytes: [
+            &[0xc3][..],
+            &[0x0f, 0x1f, 0x44, 0x00, 0x00, 0x90, 0x90],
+            &[0xc3],
+        ]
+        .concat(),
+    };
+    let original = mem.bytes.clone();
+    let site = site(0x1001, 7).with_load_bias(0x10000);
+    let call = site.x86_64_call(0x11000).unwrap();
+    let applied = site.patch(Arch::X86_64, &mut mem, &call).unwrap();
+    assert_eq!(applied.address, 0x11001);
+    assert_eq!(applied.original, original[1..8]);
+    assert_eq!(
+        mem.bytes,
+        [0xc3, 0xe8, 0xfa, 0xff, 0xff, 0xff, 0x90, 0x90, 0xc3]
+    );
+
+    // The shadow does not consist of NOPs anymore.
+    assert_eq!(
+        site.patch(Arch::X86_64, &mut mem, &call),
+        Err(PatchError::NotNops(0x11001))
+    );
+
+    applied.restore(&mut mem).unwrap();
+    assert_eq!(mem.bytes, original);
+}
+
+#[test]
+fn modified_patch_is_not_restored() {
+    let mut mem = Memory {
+        address: 0x1000,
+        bytes: vec![0x90; 8],
+    };
+    let site = site(0x1000, 8);
+    let applied = site
+        .patch(Arch::X86_64, &mut mem, &site.x86_64_jmp(0x2000).unwrap())
+        .unwrap();
+    mem.bytes[6] = 0xcc;
+    assert_eq!(applied.restore(&mut mem), Err(PatchError::Modified(0x1000)));
+    assert_eq!(mem.bytes[..5], applied.patched[..5]);
+}
+
+#[test]
+fn invalid_patches_are_rejected() {
+    let nop = [0x1f, 0x20, 0x03, 0xd5];
+    let mut mem = Memory {
+        address: 0x1000,
+        bytes: nop.repeat(2),
+    };
+    let site = site(0x1000, 8);
+    assert_eq!(
+        site.patch(Arch::AArch64, &mut mem, &[0; 12]),
+        Err(PatchError::TooLarge {
+            size: 12,
+            shadow_size: 8,
+        })
+    );
+    assert_eq!(
+        site.patch(Arch::AArch64, &mut mem, &[0; 6]),
+        Err(PatchError::UnalignedPadding(2))
+    );
+    assert_eq!(
+        site.with_load_bias(4)
+            .patch(Arch::AArch64, &mut mem, &[0; 4]),
+        Err(PatchError::MemoryUnreadable(0x1004))
+    );
+    assert_eq!(mem.bytes, nop.repeat(2));
+
+    let branch = [0x00, 0x00, 0x00, 0x14];
+    site.patch(Arch::AArch64, &mut mem, &branch).unwrap();
+    assert_eq!(mem.bytes, [branch, nop].concat());
+}
+
+#[cfg(feature = "from-elf")]
+#[test]
+fn images_of_objects_with_multiple_code_sections_are_rejected() {
+    use llvm_stackmap::{ElfImage, ParsingError};
+
+    assert!(ElfImage::from_path(common::fixture("stackmaps.o")).is_ok());
+    match ElfImage::from_path(common::fixture("stackmaps-sections.o")) {
+        Err(ParsingError::Malformed(msg)) => assert!(msg.contains("executable sections")),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "from-pe")]
+
+use std::fs;
+
+use llvm_stackmap::{ParseOptions, ParsingError, StackMap};
+
+mod common;
+use common::fixture;
+
+/// Offset of the `Type` field of the first relocation of the stack map section
+/// in `stackmaps.obj`.
+const FIRST_RELOC_TYPE_OFFSET: usize = 0x2aa + 8;
+
+#[test]
+fn object_is_relocated() {
+    let maps = StackMap::from_pe_path(fixture("stackmaps.obj"), &ParseOptions::default()).unwrap();
+    assert_eq!(maps.len(), 1);
+    let functions = maps[0]
+        .functions()
+        .iter()
+        .map(|f| (maps[0].function_name(f), f.function_address))
+        .collect::<Vec<_>>();
+    assert_eq!(functions, [(Some("foo"), 0x0), (Some("bar"), 0x40)]);
+}
+
+#[test]
+fn unsupported_relocation_is_rejected() {
+    let mut bytes = fs::read(fixture("stackmaps.obj")).unwrap();
+    let typ = &mut bytes[FIRST_RELOC_TYPE_OFFSET..FIRST_RELOC_TYPE_OFFSET + 2];
+    assert_eq!(typ, 1u16.to_le_bytes(), "expected IMAGE_REL_AMD64_ADDR64");
+    // IMAGE_REL_AMD64_ADDR32
+    typ.copy_from_slice(&2u16.to_le_bytes());
+
+    match StackMap::from_pe_bytes(&bytes, &ParseOptions::default()) {
+        Err(ParsingError::Malformed(msg)) => assert!(msg.contains("Unsupported relocation")),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+/// Get the (name, address) of the functions of the only stackmap of `maps`.
+fn functions(maps: &[StackMap]) -> Vec<(Option<&str>, u64)> {
+    assert_eq!(maps.len(), 1);
+    maps[0]
+        .functions()
+        .iter()
+        .map(|f| (maps[0].function_name(f), f.function_address))
+        .collect()
+}
+
+#[test]
+fn image_is_relocated_to_rvas() {
+    // The function addresses are relocated via IMAGE_REL_BASED_DIR64.
+    let maps = StackMap::from_pe_path(fixture("stackmaps.dll"), &ParseOptions::default()).unwrap();
+    assert_eq!(
+        functions(&maps),
+        [(Some("foo"), 0x1000), (Some("bar"), 0x1040)]
+    );
+}
+
+#[test]
+fn image_without_base_relocations_uses_rvas() {
+    let maps = StackMap::from_pe_path(fixture("stackmaps.exe"), &ParseOptions::default()).unwrap();
+    assert_eq!(
+        functions(&maps),
+        [(Some("foo"), 0x1000), (Some("bar"), 0x1040)]
+    );
+}
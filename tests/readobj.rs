@@ -0,0 +1,70 @@
+#![cfg(feature = "from-elf")]
+
+use std::fs;
+
+use llvm_stackmap::{Location, LocationType, StackMap, StkMapRecord, StkSizeRecord};
+
+mod common;
+use common::fixture;
+
+/// Get the stack map part of the output of `llvm-readobj --stackmap`, i.e.,
+/// without the leading file header.
+fn expected_readobj(name: &str) -> String {
+    let output = fs::read_to_string(fixture(name)).unwrap();
+    let start = output.find("LLVM StackMap Version").unwrap();
+    output[start..].to_owned()
+}
+
+#[test]
+fn display_matches_llvm_readobj() {
+    let maps = StackMap::from_path(fixture("stackmaps")).unwrap();
+    assert_eq!(maps.len(), 1);
+    assert_eq!(
+        maps[0].to_string(),
+        expected_readobj("stackmaps.readobj.txt")
+    );
+}
+
+#[test]
+fn write_readobj_matches_llvm_readobj() {
+    let maps = StackMap::from_path(fixture("stackmaps")).unwrap();
+    let mut out = Vec::new();
+    maps[0].write_readobj(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        expected_readobj("stackmaps.readobj.txt")
+    );
+}
+
+#[test]
+fn invalid_locations_are_formatted() {
+    let invalid = Location {
+        loc_type: LocationType::Invalid,
+        loc_size: 8,
+        dwarf_regnum: 7,
+        offset_or_constant: -8,
+        ..Default::default()
+    };
+    let out_of_range = Location {
+        loc_type: LocationType::ConstIndex,
+        loc_size: 8,
+        offset_or_constant: 1,
+        ..Default::default()
+    };
+    let record = StkMapRecord::new(5, 4, vec![invalid, out_of_range], vec![]).unwrap();
+    let mut map = StackMap::default();
+    map.push_constant(1 << 40).unwrap();
+    map.push_function(StkSizeRecord::default(), vec![record])
+        .unwrap();
+
+    let output = map.to_string();
+    assert!(output.contains(
+        "      #1: <invalid location> R#7 + -8, size: 8\n      \
+         #2: ConstantIndex #1 (<out of range>), size: 8\n"
+    ));
+    // Without the constants, the value is not resolved.
+    assert_eq!(
+        map.stk_map_records[0].locations[1].to_string(),
+        "ConstantIndex #1, size: 8"
+    );
+}
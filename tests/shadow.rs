@@ -0,0 +1,145 @@
+use llvm_stackmap::{Arch, Code, ShadowIssue, StackMap, StkMapRecord, StkSizeRecord};
+
+mod common;
+
+/// The multi-byte NOPs recommended by the Intel SDM, as emitted by assemblers.
+const X86_64_NOPS: &[&[u8]] = &[
+    &[0x90],
+    &[0x66, 0x90],
+    &[0x0f, 0x1f, 0x00],
+    &[0x0f, 0x1f, 0x40, 0x00],
+    &[0x0f, 0x1f, 0x44, 0x00, 0x00],
+    &[0x66, 0x0f, 0x1f, 0x44, 0x00, 0x00],
+    &[0x0f, 0x1f, 0x80, 0x00, 0x00, 0x00, 0x00],
+    &[0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+    &[0x66, 0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+    &[0x66, 0x2e, 0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+    &[
+        0x66, 0x66, 0x2e, 0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+];
+
+#[test]
+fn x86_64_nops_are_decoded() {
+    for nop in X86_64_NOPS {
+        let mut code = nop.to_vec();
+        code.push(0xc3);
+        assert_eq!(Arch::X86_64.nop_len(&code), Some(nop.len()), "{:x?}", nop);
+    }
+    // nop [rip + disp32] and nop [disp32] via SIB without base
+    let rip_relative = [0x0f, 0x1f, 0x05, 0x00, 0x00, 0x00, 0x00];
+    assert_eq!(Arch::X86_64.nop_len(&rip_relative), Some(7));
+    let absolute = [0x0f, 0x1f, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00];
+    assert_eq!(Arch::X86_64.nop_len(&absolute), Some(8));
+}
+
+#[test]
+fn x86_64_non_nops_are_rejected() {
+    // ret, prefixes without opcode, other 0x0f opcodes and truncated NOPs
+    for code in [
+        &[0xc3][..],
+        &[0x66, 0x2e],
+        &[0x0f, 0x0b],
+        &[0x0f, 0x1f],
+        &[0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00],
+        &[],
+    ] {
+        assert_eq!(Arch::X86_64.nop_len(code), None, "{:x?}", code);
+    }
+}
+
+#[test]
+fn nop_sleds_are_measured() {
+    let mut code = X86_64_NOPS.concat();
+    let sled_len = code.len();
+    code.extend_from_slice(&[0xc3, 0x90]);
+    assert_eq!(Arch::X86_64.nop_sled_len(&code), sled_len);
+
+    let nop = [0x1f, 0x20, 0x03, 0xd5];
+    let ret = [0xc0, 0x03, 0x5f, 0xd6];
+    assert_eq!(Arch::AArch64.nop_len(&nop), Some(4));
+    assert_eq!(Arch::AArch64.nop_len(&nop[..3]), None);
+    assert_eq!(Arch::AArch64.nop_sled_len(&[nop, nop, ret].concat()), 8);
+}
+
+#[test]
+fn shadow_issues_are_reported() {
+    let mut map = StackMap::default();
+    let function = StkSizeRecord {
+        function_address: 0x1000,
+        ..Default::default()
+    };
+    let records = [0, 8, 10, 0x100]
+        .into_iter()
+        .map(|offset| StkMapRecord::new(offset.into(), offset, vec![], vec![]).unwrap())
+        .collect();
+    map.push_function(function, records).unwrap();
+
+    // 8 bytes of NOPs, a ret and 4 bytes of NOPs
+    let mut bytes = vec![0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc3, 0xc3];
+    bytes.extend_from_slice(&[0x0f, 0x1f, 0x40, 0x00]);
+    let code = Code::new(Arch::X86_64).with_section(0x1000, bytes);
+    assert_eq!(code.shadow_at(0x1000).unwrap().nop_len, 8);
+
+    let issues = map.verify_shadows(&code, |record| match record.patch_point_id {
+        0 | 10 => 4,
+        _ => 0,
+    });
+    assert_eq!(
+        issues,
+        [ShadowIssue::NotInCode {
+            record_idx: 3,
+            address: 0x1100,
+        },]
+    );
+
+    let issues = map.verify_shadows(&code, |record| match record.patch_point_id {
+        0 => 12,
+        8 => 1,
+        _ => 0,
+    });
+    assert_eq!(
+        issues,
+        [
+            ShadowIssue::ShadowTooShort {
+                record_idx: 0,
+                address: 0x1000,
+                expected: 12,
+                nop_len: 8,
+            },
+            ShadowIssue::ShadowOverlap {
+                record_idx: 0,
+                other_record_idx: 1,
+                other_address: 0x1008,
+            },
+            ShadowIssue::ShadowOverlap {
+                record_idx: 0,
+                other_record_idx: 2,
+                other_address: 0x100a,
+            },
+            ShadowIssue::ShadowTooShort {
+                record_idx: 1,
+                address: 0x1008,
+                expected: 1,
+                nop_len: 0,
+            },
+            ShadowIssue::NotInCode {
+                record_idx: 3,
+                address: 0x1100,
+            },
+        ]
+    );
+}
+
+#[cfg(feature = "from-elf")]
+#[test]
+fn objects_with_multiple_code_sections_are_rejected() {
+    use llvm_stackmap::ParsingError;
+
+    let code = Code::from_path(common::fixture("stackmaps.o")).unwrap();
+    assert!(code.code_at(0).is_some());
+    match Code::from_path(common::fixture("stackmaps-sections.o")) {
+        Err(ParsingError::Malformed(msg)) => assert!(msg.contains("executable sections")),
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "dwarf")]
+
+use llvm_stackmap::{SourceResolver, StackMap};
+
+mod common;
+use common::fixture;
+
+#[test]
+fn record_is_resolved_to_its_source_line() {
+    let maps = StackMap::from_path(fixture("source")).unwrap();
+    let resolver = SourceResolver::from_path(fixture("source")).unwrap();
+    let function = &maps[0].functions()[0];
+    let record = &maps[0].records()[0];
+    assert_eq!(maps[0].function_name(function), Some("foo"));
+
+    let location = record
+        .source_location(function, &resolver)
+        .unwrap()
+        .unwrap();
+    assert_eq!(location.file(), Some("/src/source.c"));
+    assert_eq!(location.line(), Some(6));
+    assert_eq!(location.column(), Some(3));
+    assert_eq!(location.frames.len(), 1);
+    assert_eq!(
+        location.innermost().unwrap().function.as_deref(),
+        Some("foo")
+    );
+
+    // There is no debug info for the headers of the file.
+    assert_eq!(resolver.locate(0x10).unwrap(), None);
+}
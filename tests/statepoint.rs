@@ -0,0 +1,102 @@
+use llvm_stackmap::{Location, LocationType, StatepointError, StkMapRecord};
+
+mod common;
+
+fn location(loc_type: LocationType, dwarf_regnum: u16, offset_or_constant: i32) -> Location {
+    Location {
+        loc_type,
+        loc_size: 8,
+        dwarf_regnum,
+        offset_or_constant,
+        ..Default::default()
+    }
+}
+
+fn constant(value: i32) -> Location {
+    location(LocationType::Constant, 0, value)
+}
+
+fn record(locations: Vec<Location>) -> StkMapRecord {
+    StkMapRecord::new(42, 0, locations, vec![]).unwrap()
+}
+
+#[cfg(feature = "from-elf")]
+#[test]
+fn statepoint_of_object_is_decoded() {
+    use llvm_stackmap::StackMap;
+
+    let maps = StackMap::from_path(common::fixture("statepoint.o")).unwrap();
+    let statepoint = maps[0].stk_map_records[0].as_statepoint().unwrap();
+    assert_eq!(statepoint.id(), 42);
+    assert_eq!(statepoint.calling_convention(), 0);
+    assert_eq!(statepoint.flags(), 1);
+    assert!(statepoint.is_gc_transition());
+    assert!(!statepoint.is_deopt_live_in());
+
+    let spilled = location(LocationType::Indirect, 7, 8);
+    assert_eq!(
+        statepoint.deopt_locations(),
+        [location(LocationType::Indirect, 7, 16), constant(7)]
+    );
+    assert_eq!(statepoint.gc_pointer_count(), 1);
+    let pair = statepoint.gc_pointer_pairs().next().unwrap();
+    assert_eq!((*pair.base, *pair.derived), (spilled, spilled));
+}
+
+#[test]
+fn gc_pointers_follow_deopt_locations() {
+    let base = location(LocationType::Register, 3, 0);
+    let derived = location(LocationType::Indirect, 7, 8);
+    let record = record(vec![
+        constant(0),
+        constant(2),
+        constant(1),
+        constant(5),
+        base,
+        derived,
+    ]);
+    let statepoint = record.as_statepoint().unwrap();
+    assert!(statepoint.is_deopt_live_in());
+    assert_eq!(statepoint.deopt_locations(), [constant(5)]);
+    let pairs = statepoint
+        .gc_pointer_pairs()
+        .map(|pair| (*pair.base, *pair.derived))
+        .collect::<Vec<_>>();
+    assert_eq!(pairs, [(base, derived)]);
+}
+
+#[test]
+fn malformed_statepoints_are_rejected() {
+    let register = location(LocationType::Register, 3, 0);
+    let cases = [
+        (
+            vec![constant(0), constant(0)],
+            StatepointError::MissingHeader(2),
+        ),
+        (
+            vec![constant(0), register, constant(0)],
+            StatepointError::NonConstantHeader(1),
+        ),
+        (
+            vec![constant(0), constant(0), constant(2), register],
+            StatepointError::DeoptLocationsOutOfBounds {
+                num_deopt: 2,
+                num_remaining: 1,
+            },
+        ),
+        (
+            vec![constant(0), constant(0), constant(-1)],
+            StatepointError::DeoptLocationsOutOfBounds {
+                num_deopt: -1,
+                num_remaining: 0,
+            },
+        ),
+        (
+            vec![constant(0), constant(0), constant(0), register],
+            StatepointError::UnpairedGcPointer,
+        ),
+    ];
+    for (locations, error) in cases {
+        assert_eq!(record(locations).as_statepoint().unwrap_err(), error);
+    }
+}
@@ -0,0 +1,83 @@
+use llvm_stackmap::{StackMap, StkSizeRecord, Symbol, Symbols};
+
+mod common;
+
+fn symbol(name: &str, address: u64) -> Symbol {
+    Symbol {
+        name: name.to_owned(),
+        address,
+        size: 0,
+    }
+}
+
+#[test]
+fn stk_size_record_is_plain_data() {
+    fn assert_copy<T: Copy>() {}
+    assert_copy::<StkSizeRecord>();
+    assert_eq!(std::mem::size_of::<StkSizeRecord>(), 24);
+}
+
+#[test]
+fn symbolize_only_keeps_function_symbols() {
+    let mut map = StackMap::default();
+    let function = StkSizeRecord {
+        function_address: 0x1000,
+        ..Default::default()
+    };
+    map.push_function(function, vec![]).unwrap();
+
+    let symbols: Symbols = [symbol("foo", 0x1000), symbol("bar", 0x2000)]
+        .into_iter()
+        .collect();
+    map.symbolize(&symbols);
+    assert_eq!(map.symbols().len(), 1);
+    assert_eq!(map.function_name(&map.functions()[0]), Some("foo"));
+    assert!(map.function_by_name("foo").is_some());
+    assert!(map.function_by_name("bar").is_none());
+}
+
+#[cfg(feature = "from-elf")]
+#[test]
+fn from_path_symbolizes_functions() {
+    let maps = StackMap::from_path(common::fixture("stackmaps")).unwrap();
+    let names = maps[0]
+        .functions()
+        .iter()
+        .map(|function| maps[0].function_name(function))
+        .collect::<Vec<_>>();
+    assert_eq!(names, [Some("foo"), Some("bar")]);
+
+    let (function, records) = maps[0].function_by_name("bar").unwrap();
+    assert_eq!(function.function_address, 0x1e0);
+    assert_eq!(records[0].patch_point_id, 3);
+}
+
+#[cfg(feature = "from-elf")]
+#[test]
+fn debug_file_lookup_is_opt_in() {
+    use llvm_stackmap::DebugFileLocator;
+
+    let path = common::fixture("stackmaps.stripped");
+    // The debug file is found via `.gnu_debuglink`.
+    let bytes = std::fs::read(&path).unwrap();
+    let elf = goblin::elf::Elf::parse(&bytes).unwrap();
+    assert_eq!(DebugFileLocator::build_id(&elf, &bytes), None);
+
+    let maps = StackMap::from_path(&path).unwrap();
+    assert!(maps[0].symbols().is_empty());
+
+    let locator = DebugFileLocator::new(vec![]);
+    let maps = StackMap::from_path_with(&path, &locator).unwrap();
+    assert_eq!(maps[0].function_name(&maps[0].functions()[0]), Some("foo"));
+}
+
+#[test]
+fn merged_symbols_take_precedence() {
+    let mut symbols: Symbols = [symbol("dynamic", 0x1000), symbol("kept", 0x2000)]
+        .into_iter()
+        .collect();
+    let debug_symbols: Symbols = [symbol("static", 0x1000)].into_iter().collect();
+    symbols.merge(debug_symbols);
+    assert_eq!(symbols.get(0x1000).unwrap().name, "static");
+    assert_eq!(symbols.get(0x2000).unwrap().name, "kept");
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use llvm_stackmap::{
+    Arch, LiveOut, Location, LocationType, StackMap, StkMapRecord, StkSizeRecord,
+    ValidationContext, ValidationIssue,
+};
+
+fn location(loc_type: LocationType, loc_size: u16, dwarf_regnum: u16) -> Location {
+    Location {
+        loc_type,
+        loc_size,
+        dwarf_regnum,
+        ..Default::default()
+    }
+}
+
+/// A map with one function at 0x1000, whose single record contains `locations`.
+fn map_with(locations: Vec<Location>) -> StackMap {
+    let mut map = StackMap::default();
+    map.push_constant(1 << 40).unwrap();
+    let function = StkSizeRecord {
+        function_address: 0x1000,
+        stack_size: 16,
+        ..Default::default()
+    };
+    let record = StkMapRecord::new(1, 4, locations, vec![LiveOut::new(3, 8)]).unwrap();
+    map.push_function(function, vec![record]).unwrap();
+    map
+}
+
+fn x86_64() -> ValidationContext {
+    ValidationContext {
+        arch: Some(Arch::X86_64),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn consistent_map_has_no_issues() {
+    let map = map_with(vec![
+        location(LocationType::Register, 8, 3),
+        location(LocationType::Register, 32, 17),
+        location(LocationType::Direct, 8, 7),
+        location(LocationType::Indirect, 16, 6),
+        location(LocationType::Constant, 8, 0),
+        location(LocationType::ConstIndex, 8, 0),
+    ]);
+    assert_eq!(map.validate(), []);
+    assert_eq!(map.validate_with(&x86_64()), []);
+}
+
+#[test]
+fn invalid_location() {
+    let map = map_with(vec![location(LocationType::Invalid, 8, 0)]);
+    assert_eq!(
+        map.validate(),
+        [ValidationIssue::InvalidLocation {
+            record_idx: 0,
+            location_idx: 0,
+        }]
+    );
+}
+
+#[test]
+fn const_index_out_of_bounds() {
+    let mut loc = location(LocationType::ConstIndex, 8, 0);
+    loc.offset_or_constant = 1;
+    let mut negative = loc;
+    negative.offset_or_constant = -1;
+    let map = map_with(vec![loc, negative]);
+    assert_eq!(
+        map.validate(),
+        [
+            ValidationIssue::ConstIndexOutOfBounds {
+                record_idx: 0,
+                location_idx: 0,
+                index: 1,
+            },
+            ValidationIssue::ConstIndexOutOfBounds {
+                record_idx: 0,
+                location_idx: 1,
+                index: -1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn header_count_mismatches() {
+    let mut map = map_with(vec![]);
+    map.num_functions = 2;
+    map.num_constants = 0;
+    map.num_records = 3;
+    assert_eq!(
+        map.validate(),
+        [
+            ValidationIssue::NumFunctionsMismatch {
+                num_functions: 2,
+                actual: 1,
+            },
+            ValidationIssue::NumConstantsMismatch {
+                num_constants: 0,
+                actual: 1,
+            },
+            ValidationIssue::NumRecordsMismatch {
+                num_records: 3,
+                actual: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn record_count_mismatch() {
+    let mut map = map_with(vec![]);
+    map.stk_size_records[0].record_count = 2;
+    assert_eq!(
+        map.validate(),
+        [ValidationIssue::RecordCountMismatch {
+            record_count_sum: 2,
+            actual: 1,
+        }]
+    );
+}
+
+#[test]
+fn record_count_mismatches() {
+    let mut map = map_with(vec![location(LocationType::Register, 8, 3)]);
+    map.stk_map_records[0].num_locations = 2;
+    map.stk_map_records[0].num_live_outs = 0;
+    assert_eq!(
+        map.validate(),
+        [
+            ValidationIssue::NumLocationsMismatch {
+                record_idx: 0,
+                num_locations: 2,
+                actual: 1,
+            },
+            ValidationIssue::NumLiveOutsMismatch {
+                record_idx: 0,
+                num_live_outs: 0,
+                actual: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn unknown_registers() {
+    let mut map = map_with(vec![
+        location(LocationType::Register, 8, 40),
+        location(LocationType::Indirect, 8, 100),
+    ]);
+    map.stk_map_records[0].live_outs[0] = LiveOut::new(99, 8);
+    // Without an architecture, registers are not checked.
+    assert_eq!(map.validate(), []);
+    assert_eq!(
+        map.validate_with(&x86_64()),
+        [
+            ValidationIssue::UnknownLocationRegister {
+                record_idx: 0,
+                location_idx: 0,
+                dwarf_regnum: 40,
+            },
+            ValidationIssue::UnknownLocationRegister {
+                record_idx: 0,
+                location_idx: 1,
+                dwarf_regnum: 100,
+            },
+            ValidationIssue::UnknownLiveOutRegister {
+                record_idx: 0,
+                live_out_idx: 0,
+                dwarf_regnum: 99,
+            },
+        ]
+    );
+}
+
+#[test]
+fn invalid_location_registers() {
+    // rip as register and base, and xmm0 as base of Direct and Indirect locations
+    let map = map_with(vec![
+        location(LocationType::Register, 8, 16),
+        location(LocationType::Indirect, 8, 16),
+        location(LocationType::Direct, 8, 17),
+        location(LocationType::Indirect, 8, 17),
+    ]);
+    let issues = (0..4)
+        .map(|location_idx| ValidationIssue::InvalidLocationRegister {
+            record_idx: 0,
+            location_idx,
+            dwarf_regnum: if location_idx < 2 { 16 } else { 17 },
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(map.validate_with(&x86_64()), issues);
+
+    // The AArch64 stack pointer is a valid base.
+    let map = map_with(vec![location(LocationType::Indirect, 8, 31)]);
+    let aarch64 = ValidationContext {
+        arch: Some(Arch::AArch64),
+        ..Default::default()
+    };
+    assert_eq!(map.validate_with(&aarch64), []);
+}
+
+#[test]
+fn zero_location_size() {
+    let map = map_with(vec![location(LocationType::Register, 0, 3)]);
+    assert_eq!(
+        map.validate(),
+        [ValidationIssue::ZeroLocationSize {
+            record_idx: 0,
+            location_idx: 0,
+        }]
+    );
+}
+
+#[test]
+fn location_size_mismatch() {
+    let map = map_with(vec![
+        location(LocationType::Register, 16, 3),
+        location(LocationType::Direct, 16, 7),
+        // Spilled ymm register
+        location(LocationType::Indirect, 32, 7),
+    ]);
+    assert_eq!(
+        map.validate_with(&x86_64()),
+        [
+            ValidationIssue::LocationSizeMismatch {
+                record_idx: 0,
+                location_idx: 0,
+                loc_size: 16,
+                register_size: 8,
+            },
+            ValidationIssue::LocationSizeMismatch {
+                record_idx: 0,
+                location_idx: 1,
+                loc_size: 16,
+                register_size: 8,
+            },
+        ]
+    );
+}
+
+#[test]
+fn constant_with_register() {
+    let map = map_with(vec![location(LocationType::Constant, 8, 3)]);
+    assert_eq!(
+        map.validate(),
+        [ValidationIssue::ConstantWithRegister {
+            record_idx: 0,
+            location_idx: 0,
+            dwarf_regnum: 3,
+        }]
+    );
+}
+
+#[test]
+fn instruction_offset_out_of_function() {
+    let map = map_with(vec![]);
+    let ctx = ValidationContext {
+        function_sizes: HashMap::from([(0x1000, 4)]),
+        ..Default::default()
+    };
+    assert_eq!(
+        map.validate_with(&ctx),
+        [ValidationIssue::InstructionOffsetOutOfFunction {
+            record_idx: 0,
+            function_address: 0x1000,
+            instruction_offset: 4,
+            function_size: 4,
+        }]
+    );
+}
+
+#[test]
+fn duplicate_function_address() {
+    let mut map = map_with(vec![]);
+    let function = map.stk_size_records[0];
+    map.push_function(function, vec![]).unwrap();
+    map.stk_size_records[1].record_count = 0;
+    assert_eq!(
+        map.validate(),
+        [ValidationIssue::DuplicateFunctionAddress {
+            function_idx: 1,
+            function_address: 0x1000,
+        }]
+    );
+}
@@ -0,0 +1,153 @@
+use llvm_stackmap::{
+    Arch, LiveOut, Location, LocationType, RegisterProvider, Value, VectorRegisters,
+};
+
+/// The DWARF register numbers of xmm0/ymm0, xmm15/ymm15, v0 and v31.
+const XMM0: u16 = 17;
+const XMM15: u16 = 32;
+const V0: u16 = 64;
+const V31: u16 = 95;
+
+const XSTATE_SSE: u64 = 1 << 1;
+const XSTATE_AVX: u64 = 1 << 2;
+
+/// The lower 16 bytes of vector register `idx`: 0xi0, 0xi1, ...
+fn lower(idx: usize) -> Vec<u8> {
+    (0..16).map(|byte| (idx << 4 | byte) as u8).collect()
+}
+
+/// The upper 16 bytes of ymm register `idx`: 0x80 + idx repeated.
+fn upper(idx: usize) -> Vec<u8> {
+    vec![0x80 + idx as u8; 16]
+}
+
+/// An FXSAVE area with `lower(idx)` in the xmm registers, which start at 160.
+fn fxsave() -> Vec<u8> {
+    let mut area = vec![0xff; 512];
+    for idx in 0..16 {
+        area[160 + idx * 16..176 + idx * 16].copy_from_slice(&lower(idx));
+    }
+    area
+}
+
+/// A standard format XSAVE area with the given XSTATE_BV and `upper(idx)` in the
+/// upper halves of the ymm registers, which start at 576.
+fn xsave(xstate_bv: u64) -> Vec<u8> {
+    let mut area = fxsave();
+    area.resize(576 + 16 * 16, 0);
+    area[512..520].copy_from_slice(&xstate_bv.to_le_bytes());
+    for idx in 0..16 {
+        area[576 + idx * 16..592 + idx * 16].copy_from_slice(&upper(idx));
+    }
+    area
+}
+
+#[test]
+fn xmm_registers_are_read_from_fxsave() {
+    let regs = VectorRegisters::from_fxsave(&fxsave()).unwrap();
+    assert_eq!(regs.get(XMM0), Some(lower(0).as_slice()));
+    assert_eq!(regs.get(XMM15), Some(lower(15).as_slice()));
+    assert_eq!(regs.get(XMM15 + 1), None);
+    assert_eq!(regs.read_register(XMM0 + 1), Some(0x1716151413121110));
+
+    assert_eq!(VectorRegisters::from_fxsave(&fxsave()[..511]), None);
+}
+
+#[test]
+fn ymm_registers_are_read_from_xsave() {
+    let regs = VectorRegisters::from_xsave(&xsave(XSTATE_SSE | XSTATE_AVX)).unwrap();
+    for (idx, regnum) in [(0, XMM0), (15, XMM15)] {
+        assert_eq!(
+            regs.get(regnum),
+            Some([lower(idx), upper(idx)].concat().as_slice())
+        );
+    }
+
+    // Components not in XSTATE_BV are zero.
+    let regs = VectorRegisters::from_xsave(&xsave(XSTATE_SSE)).unwrap();
+    assert_eq!(
+        regs.get(XMM0),
+        Some([lower(0), vec![0; 16]].concat().as_slice())
+    );
+    let regs = VectorRegisters::from_xsave(&xsave(XSTATE_AVX)).unwrap();
+    assert_eq!(
+        regs.get(XMM0),
+        Some([vec![0; 16], upper(0)].concat().as_slice())
+    );
+
+    // Without the AVX state, only the xmm registers are available.
+    let area = xsave(XSTATE_SSE | XSTATE_AVX);
+    let regs = VectorRegisters::from_xsave(&area[..576]).unwrap();
+    assert_eq!(regs.get(XMM15), Some(lower(15).as_slice()));
+
+    assert_eq!(VectorRegisters::from_xsave(&area[..519]), None);
+}
+
+#[test]
+fn v_registers_are_read_from_fpsimd() {
+    let area = (0..32).flat_map(lower).collect::<Vec<_>>();
+    let regs = VectorRegisters::from_aarch64_fpsimd(&area).unwrap();
+    assert_eq!(regs.get(V0), Some(lower(0).as_slice()));
+    assert_eq!(regs.get(V31), Some(lower(31).as_slice()));
+    assert_eq!(VectorRegisters::from_aarch64_fpsimd(&area[..511]), None);
+}
+
+#[test]
+fn vector_locations_are_evaluated() {
+    let vector = VectorRegisters::from_xsave(&xsave(XSTATE_SSE | XSTATE_AVX)).unwrap();
+    let regs = vector.with_general(|regnum| (regnum == 3).then_some(0x2a));
+    let mem = |_, _: &mut [u8]| false;
+    let register = |dwarf_regnum, loc_size| Location {
+        loc_type: LocationType::Register,
+        loc_size,
+        dwarf_regnum,
+        ..Default::default()
+    };
+
+    let ymm0 = [lower(0), upper(0)].concat();
+    assert_eq!(
+        register(XMM0, 32).evaluate_value(&[], &regs, &mem),
+        Ok(Value::U256(ymm0.try_into().unwrap()))
+    );
+    assert_eq!(
+        register(XMM0, 16).evaluate_value(&[], &regs, &mem),
+        Ok(Value::U128(u128::from_le_bytes(
+            lower(0).try_into().unwrap()
+        )))
+    );
+    assert_eq!(
+        LiveOut::new(XMM0, 8).evaluate_value(&regs),
+        Ok(Value::U64(0x0706050403020100))
+    );
+    // General purpose registers are read from the wrapped provider.
+    assert_eq!(
+        register(3, 8).evaluate_value(&[], &regs, &mem),
+        Ok(Value::U64(0x2a))
+    );
+    assert_eq!(regs.read_register(4), None);
+}
+
+#[test]
+fn sized_register_names() {
+    let x86_64 = Arch::X86_64;
+    assert_eq!(
+        x86_64.sized_register_name(XMM0, 16).as_deref(),
+        Some("xmm0")
+    );
+    assert_eq!(x86_64.sized_register_name(XMM0, 8).as_deref(), Some("xmm0"));
+    assert_eq!(
+        x86_64.sized_register_name(XMM15, 32).as_deref(),
+        Some("ymm15")
+    );
+    assert_eq!(x86_64.sized_register_name(3, 4).as_deref(), Some("rbx"));
+    assert_eq!(x86_64.sized_register_name(XMM15 + 1, 16), None);
+
+    let aarch64 = Arch::AArch64;
+    let names = [1, 2, 4, 8, 16, 32]
+        .into_iter()
+        .map(|size| aarch64.sized_register_name(V31, size).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["b31", "h31", "s31", "d31", "q31", "v31"]);
+    assert_eq!(aarch64.sized_register_name(31, 8).as_deref(), Some("sp"));
+    assert_eq!(aarch64.sized_register_name(V31 + 1, 8), None);
+}
@@ -0,0 +1,76 @@
+use llvm_stackmap::{
+    Arch, FrameWalker, StackMap, StackMapIndex, StkMapRecord, StkSizeRecord, Symbol, Symbols,
+    WalkStop,
+};
+
+/// Two functions, where `0x1000` calls the last function `0x2000`.
+fn map() -> StackMap {
+    let mut map = StackMap::default();
+    for (function_address, stack_size, instruction_offset) in [(0x1000, 16, 4), (0x2000, 8, 0x10)] {
+        let function = StkSizeRecord {
+            function_address,
+            stack_size,
+            ..Default::default()
+        };
+        let record = StkMapRecord::new(1, instruction_offset, vec![], vec![]).unwrap();
+        map.push_function(function, vec![record]).unwrap();
+    }
+    map
+}
+
+fn function_address(function: Option<&StkSizeRecord>) -> Option<u64> {
+    function.map(|f| f.function_address)
+}
+
+#[test]
+fn end_of_last_function_is_unknown_without_symbols() {
+    let maps = [map()];
+    let index = StackMapIndex::new(&maps);
+    assert_eq!(function_address(index.function_at(0xfff)), None);
+    assert_eq!(function_address(index.function_at(0x1000)), Some(0x1000));
+    assert_eq!(function_address(index.function_at(0x1fff)), Some(0x1000));
+    assert_eq!(function_address(index.function_at(0x2000)), None);
+    assert_eq!(function_address(index.function_at(0x2010)), None);
+}
+
+#[test]
+fn symbol_sizes_determine_function_ends() {
+    let maps = [map()];
+    let symbols: Symbols = [("foo", 0x1000, 0x20), ("bar", 0x2000, 0x40)]
+        .into_iter()
+        .map(|(name, address, size)| Symbol {
+            name: name.to_owned(),
+            address,
+            size,
+        })
+        .collect();
+    let index = StackMapIndex::with_load_bias(&maps, 0x10000).with_symbols(&symbols);
+    assert_eq!(function_address(index.function_at(0x1101f)), Some(0x1000));
+    assert_eq!(function_address(index.function_at(0x11020)), None);
+    assert_eq!(function_address(index.function_at(0x1203f)), Some(0x2000));
+    assert_eq!(function_address(index.function_at(0x12040)), None);
+}
+
+#[test]
+fn walker_uses_records_of_last_function() {
+    let maps = [map()];
+    let index = StackMapIndex::new(&maps);
+    // The return addresses are stored above the frames of the callees.
+    let mem = |address: u64, buf: &mut [u8]| {
+        let value: u64 = match address {
+            0x7008 => 0x1004,
+            0x7020 => 0x9000,
+            _ => return false,
+        };
+        buf.copy_from_slice(&value.to_le_bytes());
+        true
+    };
+
+    let mut walker = FrameWalker::new(&index, Arch::X86_64, &mem, 0x2010, 0x7000);
+    let frames = walker
+        .by_ref()
+        .map(|frame| (frame.function.function_address, frame.pc, frame.sp))
+        .collect::<Vec<_>>();
+    assert_eq!(frames, [(0x2000, 0x2010, 0x7000), (0x1000, 0x1004, 0x7010)]);
+    assert_eq!(walker.stop_reason(), Some(&WalkStop::NotCovered(0x9000)));
+}